@@ -7,23 +7,72 @@ pub const SEED_COLLATERAL_ACCOUNT: &[u8] = b"collateral";
 pub const SEED_SOL_ACCOUNT: &[u8] = b"sol";
 pub const SEED_GOVERNANCE: &[u8] = b"governance";
 pub const SEED_ORACLE_CONFIG: &[u8] = b"oracle";
+pub const SEED_ORACLE_STATE: &[u8] = b"oracle_state";
+pub const SEED_COLLATERAL_CONFIG: &[u8] = b"collateral_config";
+pub const SEED_EMODE_CATEGORY: &[u8] = b"emode_category";
+pub const SEED_FACILITATOR: &[u8] = b"facilitator";
+pub const SEED_VOTE_RECORD: &[u8] = b"vote_record";
+pub const SEED_VOTER_WEIGHT: &[u8] = b"voter_weight";
+pub const SEED_GOVERNANCE_MINT: &[u8] = b"governance_mint";
+pub const SEED_GOVERNANCE_AUTHORITY: &[u8] = b"governance_authority";
 
 // I'm using Pyth Network as my primary price oracle
 #[constant]
-pub const FEED_ID: &str = "OxeredBb6fda2ceba41da15d4095dlda392a0d2f8ed0c6c7bcof4cfac8c280b56d";
+pub const FEED_ID: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
 
 // I've added a backup oracle for redundancy and security
 #[constant]
-pub const BACKUP_FEED_ID: &str = "0x1111Bb6fda2ceba41da15d4095dlda392a0d2f8ed0c6c7bcof4cfac8c280aaaa";
+pub const BACKUP_FEED_ID: &str = "0x1111bb6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280aaaa";
 
 // I don't want to use oracle prices that are too old (100 seconds max)
 pub const MAXIMUM_AGE: u64 = 100;
 
-// This helps me adjust the decimal precision from the oracle
-pub const PRICE_FEED_DECIMAL_ADJUSTMENT: u128 = 10;
+// I normalize Switchboard's 18-decimal results down to this exponent
+pub const SWITCHBOARD_PRICE_EXPONENT: i32 = -8;
 
-// I require at least 80% confidence in oracle prices
-pub const ORACLE_CONFIDENCE_THRESHOLD: u8 = 80;
+// I only accept feeds whose exponent lies in this range - anything else is rejected
+pub const MIN_PRICE_EXPONENT: i32 = -18;
+pub const MAX_PRICE_EXPONENT: i32 = 0;
+
+// By default I reject prices whose confidence interval exceeds 2% of the price
+pub const DEFAULT_MAX_CONFIDENCE_RATIO_BPS: u64 = 200;
+
+// By default I shift prices by one full confidence interval when valuing conservatively
+pub const DEFAULT_CONFIDENCE_MULTIPLIER_BPS: u64 = 10_000;
+
+// I never shift prices by more than five confidence intervals
+pub const MAX_CONFIDENCE_MULTIPLIER_BPS: u64 = 50_000;
+
+// I pause minting and withdrawals if the price moves more than 15% within the window
+pub const DEFAULT_MAX_PRICE_MOVE_BPS: u64 = 1_500;
+
+// The circuit breaker compares prices over a one hour window (in seconds)
+pub const DEFAULT_PRICE_MOVE_WINDOW: i64 = 3600;
+
+// When every oracle is stale I fall back to the last accepted price, widening its bounds
+// by 5% plus 1% for every hour it has aged, up to 50%
+pub const FALLBACK_BASE_MARGIN_BPS: u64 = 500;
+pub const FALLBACK_MARGIN_BPS_PER_HOUR: u64 = 100;
+pub const FALLBACK_MAX_MARGIN_BPS: u64 = 5_000;
+
+// I won't fall back on a price older than a week (in seconds)
+pub const FALLBACK_MAX_AGE: i64 = 604_800;
+
+// Liquid staking tokens are priced through SPL stake pools owned by this program
+pub const SPL_STAKE_POOL_PROGRAM_ID: Pubkey = pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+
+// I only trust stake pool exchange rates between 1 and 2 SOL per pool token
+pub const MIN_LST_EXCHANGE_RATE_BPS: u64 = 10_000;
+pub const MAX_LST_EXCHANGE_RATE_BPS: u64 = 20_000;
+
+// A vault can hold at most this many registered tokens alongside its SOL and stake
+pub const MAX_COLLATERAL_POSITIONS: usize = 4;
+
+// Governance can set up to this many debt-size tiers
+pub const MAX_DEBT_TIERS: usize = 4;
+
+// Category 0 means a vault (or listed token) isn't in any e-mode category
+pub const NO_EMODE_CATEGORY: u8 = 0;
 
 // If my two oracles disagree by more than 5%, something's wrong
 pub const MAX_ORACLE_PRICE_DEVIATION: u8 = 5;
@@ -31,17 +80,26 @@ pub const MAX_ORACLE_PRICE_DEVIATION: u8 = 5;
 // My stablecoin has 9 decimals, matching SOL's precision
 pub const MINT_DECIMALS: u8 = 9;
 
+// The governance token uses the same precision
+pub const GOVERNANCE_MINT_DECIMALS: u8 = 9;
+
+// I normalize every oracle price to the same precision as my stablecoin
+pub const PRICE_DECIMALS: u8 = MINT_DECIMALS;
+
 // I'll liquidate positions when collateral value falls below 50% of debt
 pub const LIQUIDATION_THRESHOLD: u64 = 50;
 
 // Liquidators get a 10% bonus to incentivize them
 pub const LIQUIDATION_BONUS: u64 = 10;
 
-// A health factor of 1 is my minimum threshold for safety
-pub const MIN_HEALTH_FACTOR: u64 = 1;
+// A health factor of 1.0 is my minimum threshold for safety (in basis points)
+pub const MIN_HEALTH_FACTOR_BPS: u64 = 10_000;
 
-// I warn users when their health factor drops below 2
-pub const CRITICAL_HEALTH_FACTOR: u64 = 2;
+// The minimum health factor can be raised to at most 3.0, so no single change makes every vault liquidatable
+pub const MAX_MIN_HEALTH_FACTOR_BPS: u64 = 30_000;
+
+// I warn users when their health factor drops below 2.0 (in basis points)
+pub const CRITICAL_HEALTH_FACTOR_BPS: u64 = 20_000;
 
 // I don't want positions to be over-collateralized beyond 300%
 pub const MAX_COLLATERAL_RATIO: u64 = 300;
@@ -49,23 +107,53 @@ pub const MAX_COLLATERAL_RATIO: u64 = 300;
 // I require at least 150% collateralization for safety
 pub const MIN_COLLATERAL_RATIO: u64 = 150;
 
+// A debt tier can add at most this many percentage points, so the largest vaults never need more than MAX_COLLATERAL_RATIO
+pub const MAX_DEBT_TIER_EXTRA_COLLATERAL_RATIO: u64 = MAX_COLLATERAL_RATIO - MIN_COLLATERAL_RATIO;
+
 // I apply a 5% discount to collateral value to account for volatility
 pub const VOLATILITY_ADJUSTMENT: u64 = 5;
 
 // I limit how many tokens can be minted per account for safety
 pub const MAX_MINT_AMOUNT: u64 = 1_000_000_000;
 
-// Users need at least 100,000 governance tokens to create proposals
-pub const MIN_PROPOSAL_THRESHOLD: u64 = 100_000;
+// By default users need at least 100,000 governance tokens of voting power to create proposals
+pub const DEFAULT_PROPOSAL_THRESHOLD: u64 = 100_000;
+
+// By default voting lasts for 24 hours (in seconds)
+pub const DEFAULT_VOTING_PERIOD: i64 = 86400;
+
+// By default there's a 12-hour delay between a proposal passing and its execution
+pub const DEFAULT_EXECUTION_DELAY: i64 = 43200;
+
+// Governance can never rush itself: voting and the execution delay each last between an hour and 30 days
+pub const MIN_VOTING_PERIOD: i64 = 3600;
+pub const MAX_VOTING_PERIOD: i64 = 2_592_000;
+pub const MIN_EXECUTION_DELAY: i64 = 3600;
+pub const MAX_EXECUTION_DELAY: i64 = 2_592_000;
+
+// A passed proposal has a week after its delay to be executed, and governance can set anywhere from a day to 30 days
+pub const DEFAULT_EXECUTION_WINDOW: i64 = 604_800;
+pub const MIN_EXECUTION_WINDOW: i64 = 86_400;
+pub const MAX_EXECUTION_WINDOW: i64 = 2_592_000;
+
+// An instruction-list proposal can run at most this many instructions
+pub const MAX_GOVERNANCE_INSTRUCTIONS: usize = 8;
 
-// Voting lasts for 24 hours (in seconds)
-pub const VOTING_PERIOD: u64 = 86400;
+// A voter weight account keeps this many balance checkpoints, dropping the oldest when full
+pub const MAX_VOTER_WEIGHT_CHECKPOINTS: usize = 32;
 
-// After a proposal passes, there's a 12-hour delay before execution
-pub const EXECUTION_DELAY: u64 = 43200;
+// Governance tokens can be locked for up to two years by default, and governance can raise that to four
+pub const DEFAULT_MAX_LOCK_DURATION: i64 = 63_072_000;
+pub const MAX_LOCK_DURATION_LIMIT: i64 = 126_144_000;
 
-// At least 10% of tokens must vote for a proposal to pass
-pub const QUORUM_THRESHOLD: u8 = 10;
+// By default the share of the governance supply that has to vote (quorum) and the share of those votes that have
+// to be in favour (approval) depend on how much damage a proposal could do (in basis points)
+pub const STANDARD_QUORUM_BPS: u64 = 1_000;
+pub const STANDARD_APPROVAL_BPS: u64 = 5_000;
+pub const RISK_QUORUM_BPS: u64 = 2_000;
+pub const RISK_APPROVAL_BPS: u64 = 6_000;
+pub const CRITICAL_QUORUM_BPS: u64 = 3_000;
+pub const CRITICAL_APPROVAL_BPS: u64 = 6_667;
 
 // I charge a small 0.1% fee on minting (in basis points)
 pub const MINT_FEE_BPS: u16 = 10;
@@ -66,4 +66,156 @@ pub enum CustomError {
     // I need to calculate fees accurately
     #[msg("Fee calculation error")]
     FeeCalculationError,
+    
+    // I only read prices from the account the oracle config points at
+    #[msg("Price account does not match the configured oracle source")]
+    InvalidPriceAccount,
+    
+    // I only understand feeds published with a sensible exponent
+    #[msg("Oracle price exponent is not supported")]
+    UnsupportedPriceExponent,
+    
+    // I never let arithmetic silently wrap around
+    #[msg("Math operation overflowed or divided by zero")]
+    MathOverflow,
+    
+    // I keep admin-set parameters within sane bounds
+    #[msg("Parameter is outside the allowed range")]
+    InvalidParameter,
+    
+    // I pause risky operations while the price is behaving abnormally
+    #[msg("Circuit breaker tripped: minting and withdrawals are paused")]
+    CircuitBreakerTripped,
+    
+    // Without a fresh oracle price I only allow operations that reduce risk
+    #[msg("Oracle is in fallback mode: only repayments, top-ups and liquidations are allowed")]
+    OracleFallbackRestricted,
+    
+    // I only accept Pyth updates whose signatures were fully verified
+    #[msg("Price update is not fully verified")]
+    UnverifiedPriceUpdate,
+    
+    // A position never goes back to a price older than one it has already used
+    #[msg("Price update is older than the one this position last used")]
+    OutdatedPriceUpdate,
+    
+    // Operations that add risk can't go ahead without a price
+    #[msg("A price update account is required for this operation")]
+    MissingPriceAccount,
+    
+    // I only read exchange rates from the stake pool pinned in the collateral config
+    #[msg("Stake pool account is invalid or doesn't match the collateral config")]
+    InvalidStakePool,
+    
+    // A wildly off exchange rate means the stake pool can't be trusted
+    #[msg("Stake pool exchange rate is outside the allowed range")]
+    ExchangeRateOutOfBounds,
+    
+    // A token account or registry entry doesn't belong to the collateral being moved
+    #[msg("Collateral mint doesn't match the vault position or registry entry")]
+    CollateralMintMismatch,
+    
+    // Valuing or moving token collateral needs its registry entry, price account and token accounts
+    #[msg("Accounts needed for this collateral were not provided")]
+    MissingCollateralAccounts,
+    
+    // I only take stake that is delegated, unlocked and fully controlled by the depositor
+    #[msg("Stake account must be delegated, unlocked and authorized to the depositor")]
+    InvalidStakeAccount,
+    
+    // Each vault holds a single stake account
+    #[msg("Vault already holds a stake account")]
+    StakeAccountAlreadyDeposited,
+    
+    // A vault only has room for a bounded number of collateral tokens
+    #[msg("Vault already holds the maximum number of collateral tokens")]
+    TooManyCollateralPositions,
+    
+    // Liquidators can only seize an asset the vault actually holds
+    #[msg("Vault holds none of the requested collateral")]
+    CollateralNotHeld,
+    
+    // A vault in e-mode needs its category account to be valued
+    #[msg("E-mode category account is missing or doesn't match the vault")]
+    MissingEModeCategory,
+    
+    // E-mode only works while everything in the vault is correlated
+    #[msg("Collateral doesn't belong to the vault's e-mode category")]
+    EModeCategoryMismatch,
+    
+    // Accounts from one market can never be used in another
+    #[msg("Account belongs to a different market")]
+    MarketMismatch,
+    
+    // A facilitator can never hold more than governance gave it room for
+    #[msg("Facilitator bucket capacity exceeded")]
+    FacilitatorCapacityExceeded,
+    
+    // A facilitator can only burn back what it minted
+    #[msg("Burn exceeds the facilitator's bucket level")]
+    FacilitatorBurnExceedsLevel,
+    
+    // Facilitator proposals need the facilitator's bucket account
+    #[msg("Facilitator account is required for this proposal")]
+    MissingFacilitatorAccount,
+    
+    // Votes are only counted once the voting period is over
+    #[msg("Proposal voting period hasn't ended yet")]
+    ProposalVotingInProgress,
+    
+    // A proposal's outcome is decided exactly once
+    #[msg("Proposal has already been finalized")]
+    ProposalAlreadyFinalized,
+    
+    // Proposals that can still pass or execute keep their account
+    #[msg("Only finished proposals can be closed")]
+    ProposalNotFinished,
+    
+    // Only a vote that's been cast can be withdrawn
+    #[msg("No vote to withdraw")]
+    NoVoteCast,
+    
+    // Locked governance tokens stay put until the lock ends
+    #[msg("Governance tokens are still locked")]
+    GovernanceTokensLocked,
+    
+    // Nothing new is minted after an emergency shutdown
+    #[msg("Market is in emergency shutdown")]
+    EmergencyShutdown,
+    
+    // Governance token emissions need the mint, the recipient and the token program
+    #[msg("Governance mint accounts are required for this proposal")]
+    MissingGovernanceMintAccounts,
+    
+    // Instruction lists can't be empty or too long, and only the governance authority can sign
+    #[msg("Invalid governance instructions")]
+    InvalidGovernanceInstructions,
+    
+    // Every account an instruction touches has to be passed in when it's executed
+    #[msg("Account required by a governance instruction is missing")]
+    MissingInstructionAccount,
+    
+    // Passed proposals have to be executed within the execution window
+    #[msg("Proposal has expired")]
+    ProposalExpired,
+    
+    // Proposers can cancel while voting is open, and the guardian until the proposal executes
+    #[msg("Not allowed to cancel this proposal")]
+    CannotCancelProposal,
+    
+    // Voters delegate to someone else, and undelegate only once they have
+    #[msg("Invalid delegate")]
+    InvalidDelegate,
+    
+    // Delegates only vote with power delegated to them at the proposal's snapshot
+    #[msg("Voter hadn't delegated to this delegate at the proposal snapshot")]
+    NotDelegated,
+    
+    // Stake pool rates go stale until the pool's epoch update has run
+    #[msg("Stake pool hasn't been updated this epoch")]
+    StakePoolNotUpdated,
+    
+    // A voter needs a lock of their own or power delegated to them
+    #[msg("Voter has no locked governance tokens and votes for no delegators")]
+    NoVotingPower,
 }
\ No newline at end of file
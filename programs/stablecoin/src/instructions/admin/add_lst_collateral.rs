@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use crate::{
+    state::{CollateralConfig, CollateralKind, Config}, CustomError, StakePoolExchangeRate, validate_collateral_params,
+    SEED_COLLATERAL_CONFIG, SEED_CONFIG_ACCOUNT, NO_EMODE_CATEGORY,
+};
+
+#[derive(Accounts)]
+pub struct AddLstCollateral<'info> {
+    // Only the protocol admin can list new collateral
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = authority,
+    )]
+    pub config_account: Account<'info, Config>,
+
+    // The liquid staking token being listed
+    pub lst_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: I parse this as an SPL stake pool and check it issues lst_mint
+    pub stake_pool: UncheckedAccount<'info>,
+
+    // I create one registry entry per LST mint
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CollateralConfig::INIT_SPACE,
+        seeds = [SEED_COLLATERAL_CONFIG, config_account.key().as_ref(), lst_mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn process_add_lst_collateral(
+    ctx: Context<AddLstCollateral>,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+) -> Result<()> {
+    validate_collateral_params(liquidation_threshold, liquidation_bonus)?;
+
+    // I make sure the pool really issues this LST and has a sane exchange rate today
+    let exchange_rate = StakePoolExchangeRate::from_account_info(&ctx.accounts.stake_pool)?;
+    require_keys_eq!(
+        exchange_rate.pool_mint,
+        ctx.accounts.lst_mint.key(),
+        CustomError::InvalidStakePool
+    );
+
+    *ctx.accounts.collateral_config = CollateralConfig {
+        mint: ctx.accounts.lst_mint.key(),
+        market: ctx.accounts.config_account.key(),
+        kind: CollateralKind::StakePoolToken,
+        price_account: ctx.accounts.stake_pool.key(),
+        feed_id: [0; 32],
+        decimals: ctx.accounts.lst_mint.decimals,
+        liquidation_threshold,
+        liquidation_bonus,
+        emode_category: NO_EMODE_CATEGORY,
+        bump: ctx.bumps.collateral_config,
+    };
+
+    msg!(
+        "LST collateral {} listed with stake pool {} ({} lamports / {} tokens), threshold={}%, bonus={}%",
+        ctx.accounts.lst_mint.key(),
+        ctx.accounts.stake_pool.key(),
+        exchange_rate.total_lamports,
+        exchange_rate.pool_token_supply,
+        liquidation_threshold,
+        liquidation_bonus
+    );
+
+    Ok(())
+}
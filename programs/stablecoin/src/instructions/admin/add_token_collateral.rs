@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use crate::{
+    state::{CollateralConfig, CollateralKind, Config}, CustomError, PythPriceSource, validate_collateral_params,
+    SEED_COLLATERAL_CONFIG, SEED_CONFIG_ACCOUNT, NO_EMODE_CATEGORY,
+};
+
+#[derive(Accounts)]
+pub struct AddTokenCollateral<'info> {
+    // Only the protocol admin can list new collateral
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = authority,
+    )]
+    pub config_account: Account<'info, Config>,
+
+    // The token being listed
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: I only check the owner here - this is the Pyth price account I pin for the token
+    #[account(owner = pyth_solana_receiver_sdk::ID @ CustomError::InvalidPriceAccount)]
+    pub price_update: UncheckedAccount<'info>,
+
+    // I create one registry entry per mint
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CollateralConfig::INIT_SPACE,
+        seeds = [SEED_COLLATERAL_CONFIG, config_account.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn process_add_token_collateral(
+    ctx: Context<AddTokenCollateral>,
+    feed_id: [u8; 32],
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+) -> Result<()> {
+    validate_collateral_params(liquidation_threshold, liquidation_bonus)?;
+
+    // I make sure the account is a fully verified Pyth update before pinning it
+    PythPriceSource::from_account_info_for_feed(&ctx.accounts.price_update, feed_id)?;
+
+    *ctx.accounts.collateral_config = CollateralConfig {
+        mint: ctx.accounts.mint.key(),
+        market: ctx.accounts.config_account.key(),
+        kind: CollateralKind::OracleToken,
+        price_account: ctx.accounts.price_update.key(),
+        feed_id,
+        decimals: ctx.accounts.mint.decimals,
+        liquidation_threshold,
+        liquidation_bonus,
+        emode_category: NO_EMODE_CATEGORY,
+        bump: ctx.bumps.collateral_config,
+    };
+
+    msg!(
+        "Token collateral {} listed with price account {}, threshold={}%, bonus={}%",
+        ctx.accounts.mint.key(),
+        ctx.accounts.price_update.key(),
+        liquidation_threshold,
+        liquidation_bonus
+    );
+
+    Ok(())
+}
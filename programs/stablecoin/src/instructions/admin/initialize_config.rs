@@ -1,10 +1,19 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::Mint;
 
-use crate::{Config, SEED_CONFIG_ACCOUNT, SEED_MINT_ACCOUNT, MINT_DECIMALS, LIQUIDATION_THRESHOLD, LIQUIDATION_BONUS, MIN_HEALTH_FACTOR};
+use crate::{
+    Config, CustomError, DebtTier, GovernanceConfig, OracleConfig, OracleSource, OracleState, TierThresholds,
+    SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, SEED_GOVERNANCE_AUTHORITY, SEED_MINT_ACCOUNT, SEED_GOVERNANCE_MINT, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE,
+    MINT_DECIMALS, GOVERNANCE_MINT_DECIMALS, LIQUIDATION_THRESHOLD, LIQUIDATION_BONUS, MIN_HEALTH_FACTOR_BPS, MAX_DEBT_TIERS, DEFAULT_MAX_LOCK_DURATION,
+    DEFAULT_MAX_CONFIDENCE_RATIO_BPS, DEFAULT_CONFIDENCE_MULTIPLIER_BPS,
+    DEFAULT_MAX_PRICE_MOVE_BPS, DEFAULT_PRICE_MOVE_WINDOW,
+    DEFAULT_VOTING_PERIOD, DEFAULT_EXECUTION_DELAY, DEFAULT_EXECUTION_WINDOW, DEFAULT_PROPOSAL_THRESHOLD,
+    STANDARD_QUORUM_BPS, STANDARD_APPROVAL_BPS, RISK_QUORUM_BPS, RISK_APPROVAL_BPS,
+    CRITICAL_QUORUM_BPS, CRITICAL_APPROVAL_BPS,
+};
 
 #[derive(Accounts)]
-#[instruction()]
+#[instruction(market_id: u64)]
 pub struct InitializeConfig<'info> {
     // The admin who's setting up the protocol
     #[account(mut)]
@@ -15,16 +24,46 @@ pub struct InitializeConfig<'info> {
         init,
         payer = authority,
         space = 8 + Config::INIT_SPACE,
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, market_id.to_le_bytes().as_ref()],
         bump,
     )]
     pub config_account: Account<'info, Config>,
 
+    // I create the oracle config so price reads know which adapter to use
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OracleConfig::INIT_SPACE,
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump,
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+
+    // I create the oracle state that remembers accepted prices for the circuit breaker
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OracleState::INIT_SPACE,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump,
+    )]
+    pub oracle_state: Account<'info, OracleState>,
+
+    // I create the governance rules with their defaults
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GovernanceConfig::INIT_SPACE,
+        seeds = [SEED_GOVERNANCE, config_account.key().as_ref()],
+        bump,
+    )]
+    pub governance_config: Box<Account<'info, GovernanceConfig>>,
+
     // I create the stablecoin mint with the specified decimals
     #[account(
         init,
         payer = authority,
-        seeds = [SEED_MINT_ACCOUNT],
+        seeds = [SEED_MINT_ACCOUNT, config_account.key().as_ref()],
         mint::decimals = MINT_DECIMALS,
         mint::authority = mint_account,
         mint::freeze_authority = mint_account,
@@ -32,29 +71,129 @@ pub struct InitializeConfig<'info> {
         bump,
     )]
     pub mint_account: InterfaceAccount<'info, Mint>,
+
+    // I create the market's governance token; only governance can mint more of it
+    #[account(
+        init,
+        payer = authority,
+        seeds = [SEED_GOVERNANCE_MINT, config_account.key().as_ref()],
+        mint::decimals = GOVERNANCE_MINT_DECIMALS,
+        mint::authority = governance_mint,
+        mint::freeze_authority = governance_mint,
+        mint::token_program = token_program,
+        bump,
+    )]
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: I only check the owner here - this is the Pyth price account I pin in the oracle config
+    #[account(owner = pyth_solana_receiver_sdk::ID @ CustomError::InvalidPriceAccount)]
+    pub price_update: UncheckedAccount<'info>,
+
+    /// CHECK: I only check the owner here - this is the Pyth account pricing the market's currency in USD,
+    /// left out for a USD market
+    #[account(owner = pyth_solana_receiver_sdk::ID @ CustomError::InvalidPriceAccount)]
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
     /// CHECK: This is the token program
     
     pub token_program: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn process_initialize_config(ctx: &mut Context<InitializeConfig>) -> Result<()> {
+pub fn process_initialize_config(
+    ctx: &mut Context<InitializeConfig>,
+    market_id: u64,
+    target_feed_id: [u8; 32],
+) -> Result<()> {
+    // A market priced in another currency needs the feed that converts it from USD
+    let (target_price_account, target_feed_id) = match &ctx.accounts.target_price_update {
+        Some(target_price_update) => {
+            require!(target_feed_id != [0; 32], CustomError::InvalidParameter);
+            (target_price_update.key(), target_feed_id)
+        },
+        None => (Pubkey::default(), [0; 32]),
+    };
+
     // I initialize the config with default parameters
     *ctx.accounts.config_account = Config {
         // The admin who can update parameters
         authority: ctx.accounts.authority.key(),
         
+        // Which market this config runs
+        market_id,
+        
         // The stablecoin mint
         mint_account: ctx.accounts.mint_account.key(),
         
+        // The governance token
+        governance_mint: ctx.accounts.governance_mint.key(),
+        
         // Default risk parameters
         liquidation_threshold: LIQUIDATION_THRESHOLD,
         liquidation_bonus: LIQUIDATION_BONUS,
-        min_health_factor: MIN_HEALTH_FACTOR,
+        min_health_factor_bps: MIN_HEALTH_FACTOR_BPS,
+        
+        // Governance sets up the debt-size tiers later
+        debt_tiers: [DebtTier::default(); MAX_DEBT_TIERS],
+        
+        // Nothing has been minted yet
+        vault_debt: 0,
+        facilitator_debt: 0,
+        
+        // Default governance lock limit, and the market starts out running
+        max_lock_duration: DEFAULT_MAX_LOCK_DURATION,
+        emergency_shutdown: false,
         
         // PDA bumps for addressing
         bump: ctx.bumps.config_account,
         bump_mint_account: ctx.bumps.mint_account,
+        bump_governance_mint: ctx.bumps.governance_mint,
+    };
+
+    // I start out reading prices from Pyth
+    *ctx.accounts.oracle_config = OracleConfig {
+        source: OracleSource::Pyth,
+        pyth_price_account: ctx.accounts.price_update.key(),
+        switchboard_feed: Pubkey::default(),
+        target_price_account,
+        target_feed_id,
+        fixed_price: 0,
+        fixed_exponent: 0,
+        max_confidence_ratio_bps: DEFAULT_MAX_CONFIDENCE_RATIO_BPS,
+        confidence_multiplier_bps: DEFAULT_CONFIDENCE_MULTIPLIER_BPS,
+        max_price_move_bps: DEFAULT_MAX_PRICE_MOVE_BPS,
+        price_move_window: DEFAULT_PRICE_MOVE_WINDOW,
+        bump: ctx.bumps.oracle_config,
+    };
+
+    // Governance starts out with the default rules
+    let market = ctx.accounts.config_account.key();
+    ctx.accounts.governance_config.set_inner(GovernanceConfig {
+        market,
+        voting_period: DEFAULT_VOTING_PERIOD,
+        execution_delay: DEFAULT_EXECUTION_DELAY,
+        execution_window: DEFAULT_EXECUTION_WINDOW,
+        // The admin guards the market until governance hands the role to a council
+        guardian: ctx.accounts.authority.key(),
+        proposal_threshold: DEFAULT_PROPOSAL_THRESHOLD,
+        standard: TierThresholds { quorum_bps: STANDARD_QUORUM_BPS, approval_bps: STANDARD_APPROVAL_BPS },
+        risk: TierThresholds { quorum_bps: RISK_QUORUM_BPS, approval_bps: RISK_APPROVAL_BPS },
+        critical: TierThresholds { quorum_bps: CRITICAL_QUORUM_BPS, approval_bps: CRITICAL_APPROVAL_BPS },
+        proposal_count: 0,
+        bump: ctx.bumps.governance_config,
+        authority_bump: Pubkey::find_program_address(&[SEED_GOVERNANCE_AUTHORITY, market.as_ref()], &crate::ID).1,
+    });
+
+    // I haven't seen any prices yet
+    *ctx.accounts.oracle_state = OracleState {
+        last_price: 0,
+        last_price_timestamp: 0,
+        reference_price: 0,
+        reference_timestamp: 0,
+        circuit_breaker_tripped: false,
+        tripped_at: 0,
+        fallback_mode: false,
+        bump: ctx.bumps.oracle_state,
     };
 
     Ok(())
@@ -1,6 +1,18 @@
 pub use initialize_config::*;
 pub use update_config::*;
+pub use set_authority::*;
+pub use update_oracle_config::*;
+pub use update_oracle_risk_params::*;
+pub use reset_circuit_breaker::*;
+pub use add_lst_collateral::*;
+pub use add_token_collateral::*;
 
 
 pub mod initialize_config; 
-pub mod update_config;
\ No newline at end of file
+pub mod update_config;
+pub mod set_authority;
+pub mod update_oracle_config;
+pub mod update_oracle_risk_params;
+pub mod reset_circuit_breaker;
+pub mod add_lst_collateral;
+pub mod add_token_collateral;
\ No newline at end of file
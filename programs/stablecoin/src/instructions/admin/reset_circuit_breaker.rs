@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+use crate::{
+    state::{Config, OracleState},
+    SEED_CONFIG_ACCOUNT, SEED_ORACLE_STATE, clear_circuit_breaker,
+};
+
+#[derive(Accounts)]
+pub struct ResetCircuitBreaker<'info> {
+    // Only the protocol admin can resume minting and withdrawals
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = authority,
+    )]
+    pub config_account: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Account<'info, OracleState>,
+}
+
+pub fn process_reset_circuit_breaker(ctx: Context<ResetCircuitBreaker>) -> Result<()> {
+    let oracle_state = &mut ctx.accounts.oracle_state;
+    let tripped_at = oracle_state.tripped_at;
+    clear_circuit_breaker(oracle_state);
+
+    msg!(
+        "Circuit breaker tripped at {} reset, reference price now {}",
+        tripped_at,
+        oracle_state.reference_price
+    );
+
+    Ok(())
+}
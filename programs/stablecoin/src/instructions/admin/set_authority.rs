@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use crate::{state::Config, CustomError, SEED_CONFIG_ACCOUNT};
+
+#[derive(Accounts)]
+pub struct SetAuthority<'info> {
+    // Only the current admin can hand the market over
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = authority,
+    )]
+    pub config_account: Account<'info, Config>,
+}
+
+pub fn process_set_authority(ctx: Context<SetAuthority>, new_authority: Pubkey) -> Result<()> {
+    // Handing the market to the default key would leave nobody able to sign for it
+    require!(new_authority != Pubkey::default(), CustomError::InvalidParameter);
+
+    // Usually the new admin is the market's governance authority PDA, after which admin instructions only
+    // run through ExecuteInstructions proposals
+    let config_account = &mut ctx.accounts.config_account;
+    msg!("Market authority moved from {} to {}", config_account.authority, new_authority);
+    config_account.authority = new_authority;
+
+    Ok(())
+}
\ No newline at end of file
@@ -1,21 +1,29 @@
 use anchor_lang::prelude::*;
-use crate::{state::Config, SEED_CONFIG_ACCOUNT};
+use crate::{state::Config, validate_min_health_factor, SEED_CONFIG_ACCOUNT};
 
 #[derive(Accounts)]
 pub struct UpdateConfig<'info> {
+    // Only the protocol admin can change the protocol's parameters
+    pub authority: Signer<'info>,
+
     // I need to access the config account to update its parameters
     #[account(
         mut,
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
         bump = config_account.bump,
+        has_one = authority,
     )]
     pub config_account: Account<'info, Config>,
 }
 
-pub fn process_update_config(ctx: Context<UpdateConfig>, min_health_factor: u64) -> Result<()> {
-    // I update the minimum health factor parameter
+pub fn process_update_config(ctx: Context<UpdateConfig>, min_health_factor_bps: u64) -> Result<()> {
+    // A minimum below 1.0 would let vaults mint into immediate liquidation, and one far above it
+    // would lock every vault out of minting
+    validate_min_health_factor(min_health_factor_bps)?;
+
+    // I update the minimum health factor parameter (10_000 = 1.0)
     let config_account = &mut ctx.accounts.config_account;
-    config_account.min_health_factor = min_health_factor;
+    config_account.min_health_factor_bps = min_health_factor_bps;
 
     Ok(())
 }
\ No newline at end of file
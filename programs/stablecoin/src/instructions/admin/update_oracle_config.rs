@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+use crate::{
+    state::{Config, OracleConfig, OracleSource}, CustomError,
+    SEED_CONFIG_ACCOUNT, SEED_ORACLE_CONFIG, MIN_PRICE_EXPONENT, MAX_PRICE_EXPONENT,
+};
+
+#[derive(Accounts)]
+pub struct UpdateOracleConfig<'info> {
+    // Only the protocol admin can switch oracle adapters
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = authority,
+    )]
+    pub config_account: Account<'info, Config>,
+
+    // I need to access the oracle config to update its parameters
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+}
+
+pub fn process_update_oracle_config(
+    ctx: Context<UpdateOracleConfig>,
+    source: OracleSource,
+    pyth_price_account: Pubkey,
+    switchboard_feed: Pubkey,
+    fixed_price: i64,
+    fixed_exponent: i32,
+) -> Result<()> {
+    // I won't read from an oracle account that hasn't actually been pinned
+    match source {
+        OracleSource::Pyth => require_keys_neq!(
+            pyth_price_account,
+            Pubkey::default(),
+            CustomError::InvalidPriceAccount
+        ),
+        OracleSource::Switchboard => require_keys_neq!(
+            switchboard_feed,
+            Pubkey::default(),
+            CustomError::InvalidPriceAccount
+        ),
+        OracleSource::Fixed => {}
+    }
+
+    // I won't switch to a fixed price that hasn't actually been set
+    if source == OracleSource::Fixed {
+        require!(fixed_price > 0, CustomError::InvalidPrice);
+        require!(
+            (MIN_PRICE_EXPONENT..=MAX_PRICE_EXPONENT).contains(&fixed_exponent),
+            CustomError::UnsupportedPriceExponent
+        );
+    }
+
+    let oracle_config = &mut ctx.accounts.oracle_config;
+    oracle_config.source = source;
+    oracle_config.pyth_price_account = pyth_price_account;
+    oracle_config.switchboard_feed = switchboard_feed;
+    oracle_config.fixed_price = fixed_price;
+    oracle_config.fixed_exponent = fixed_exponent;
+
+    msg!("Oracle source updated to {:?}", source);
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+use crate::{
+    state::{Config, OracleConfig}, CustomError,
+    SEED_CONFIG_ACCOUNT, SEED_ORACLE_CONFIG, BPS_DIVISOR, MAX_CONFIDENCE_MULTIPLIER_BPS,
+};
+
+#[derive(Accounts)]
+pub struct UpdateOracleRiskParams<'info> {
+    // Only the protocol admin can change how prices are applied
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = authority,
+    )]
+    pub config_account: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+}
+
+pub fn process_update_oracle_risk_params(
+    ctx: Context<UpdateOracleRiskParams>,
+    max_confidence_ratio_bps: u64,
+    confidence_multiplier_bps: u64,
+    max_price_move_bps: u64,
+    price_move_window: i64,
+) -> Result<()> {
+    // A confidence interval can't be tolerated at zero width, nor beyond the whole price
+    require!(
+        max_confidence_ratio_bps > 0 && max_confidence_ratio_bps <= BPS_DIVISOR as u64,
+        CustomError::InvalidParameter
+    );
+    require!(
+        confidence_multiplier_bps <= MAX_CONFIDENCE_MULTIPLIER_BPS,
+        CustomError::InvalidParameter
+    );
+
+    // The circuit breaker needs a real threshold and a real window to compare over
+    require!(max_price_move_bps > 0, CustomError::InvalidParameter);
+    require!(price_move_window > 0, CustomError::InvalidParameter);
+
+    let oracle_config = &mut ctx.accounts.oracle_config;
+    oracle_config.max_confidence_ratio_bps = max_confidence_ratio_bps;
+    oracle_config.confidence_multiplier_bps = confidence_multiplier_bps;
+    oracle_config.max_price_move_bps = max_price_move_bps;
+    oracle_config.price_move_window = price_move_window;
+
+    msg!(
+        "Oracle risk params updated: max confidence ratio={} bps, multiplier={} bps, max move={} bps over {}s",
+        max_confidence_ratio_bps,
+        confidence_multiplier_bps,
+        max_price_move_bps,
+        price_move_window
+    );
+
+    Ok(())
+}
@@ -1,11 +1,12 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, Token2022}};
-use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 use crate::{
-    Collateral, Config, CustomError,
-    SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, SEED_SOL_ACCOUNT, 
-    check_health_factor, deposit_sol, mint_tokens, calculate_fee,
-    MAX_MINT_AMOUNT, MINT_FEE_BPS, MIN_COLLATERAL_RATIO, MAX_COLLATERAL_RATIO
+    Collateral, Config, CustomError, Decimal, EModeCategory, OracleConfig, OracleState,
+    SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, SEED_SOL_ACCOUNT, SEED_ORACLE_CONFIG,
+    SEED_ORACLE_STATE, SEED_EMODE_CATEGORY, SEED_GOVERNANCE_AUTHORITY,
+    check_health_factor, load_asset_prices, load_emode_category, sol_emode, value_collateral, get_min_collateral_ratio, deposit_sol, mint_tokens, calculate_fee, load_price_for_operation, RiskProfile,
+    calculate_collateralization_ratio,
+    MAX_MINT_AMOUNT, MINT_FEE_BPS, MAX_COLLATERAL_RATIO
 };
 
 #[derive(Accounts)]
@@ -14,8 +15,9 @@ pub struct DepositCollateralAndMintTokens<'info> {
     pub depositer: Signer<'info>,
 
     #[account(
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
         bump = config_account.bump,
+        mut,
         has_one = mint_account,
     )]
     pub config_account: Box<Account<'info, Config>>, 
@@ -27,16 +29,23 @@ pub struct DepositCollateralAndMintTokens<'info> {
         init_if_needed,
         payer = depositer, 
         space = 8 + Collateral::INIT_SPACE,
-        seeds = [SEED_COLLATERAL_ACCOUNT, depositer.key().as_ref()],
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
         bump,
     )]
     pub collateral_account: Account<'info, Collateral>,
-    
+
+    // Only needed when the vault has opted into an e-mode category
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[collateral_account.emode_category]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+
     pub system_program: Program<'info, System>,
 
     #[account(
         mut,
-        seeds = [SEED_SOL_ACCOUNT, depositer.key().as_ref()],
+        seeds = [SEED_SOL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
         bump,
     )]
     pub sol_account: SystemAccount<'info>,
@@ -50,14 +59,50 @@ pub struct DepositCollateralAndMintTokens<'info> {
     )]
     pub token_account: InterfaceAccount<'info, TokenAccount>,
     
-    #[account(mut)]
-    pub fee_recipient: Option<SystemAccount<'info>>,
+    // Governance owns the treasury and moves its funds through ExecuteInstructions proposals
+    /// CHECK: a PDA that holds no data, I only use it as the treasury's owner
+    #[account(
+        seeds = [SEED_GOVERNANCE_AUTHORITY, config_account.key().as_ref()],
+        bump,
+    )]
+    pub governance_authority: UncheckedAccount<'info>,
+
+    // I mint the fee here, so every token a vault owes is one in circulation
+    #[account(
+        init_if_needed,
+        payer = depositer,
+        associated_token::mint = mint_account,
+        associated_token::authority = governance_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub treasury: Box<InterfaceAccount<'info, TokenAccount>>,
     
     pub token_program: Program<'info, Token2022>,
     
     pub associated_token_program: Program<'info, AssociatedToken>,
     
-    pub price_update: Account<'info, PriceUpdateV2>
+    // Only needed when minting - a plain top-up doesn't need a price
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only needed in markets that aren't denominated in USD
+    /// CHECK: I validate this against the currency feed pinned in the oracle config
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
+    // I need the oracle config to know which price adapter to use
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Box<Account<'info, OracleConfig>>,
+
+    // I record every accepted price here for the circuit breaker
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Box<Account<'info, OracleState>>,
 }
 
 pub fn process_deposit_collateral_and_mint_tokens(
@@ -69,9 +114,15 @@ pub fn process_deposit_collateral_and_mint_tokens(
         amount_to_mint <= MAX_MINT_AMOUNT,
         CustomError::ExcessiveMintAmount
     );
+    require!(
+        amount_to_mint == 0 || !ctx.accounts.config_account.emergency_shutdown,
+        CustomError::EmergencyShutdown
+    );
 
     let mint_fee = calculate_fee(amount_to_mint, MINT_FEE_BPS)?;
-    let adjusted_mint_amount = amount_to_mint - mint_fee;
+    let adjusted_mint_amount = amount_to_mint
+        .checked_sub(mint_fee)
+        .ok_or(error!(CustomError::MathOverflow))?;
     
     msg!(
         "Deposit initiated: Collateral={}, Mint={}, Fee={}",
@@ -80,11 +131,14 @@ pub fn process_deposit_collateral_and_mint_tokens(
         mint_fee
     );
 
-    let new_lamport_balance = ctx.accounts.sol_account.lamports() + amount_collateral;
+    let new_lamport_balance = ctx.accounts.sol_account.lamports()
+        .checked_add(amount_collateral)
+        .ok_or(error!(CustomError::MathOverflow))?;
     
     if !ctx.accounts.collateral_account.is_initialized {
         ctx.accounts.collateral_account.is_initialized = true;
         ctx.accounts.collateral_account.depositer = ctx.accounts.depositer.key();
+        ctx.accounts.collateral_account.market = ctx.accounts.config_account.key();
         ctx.accounts.collateral_account.sol_account = ctx.accounts.sol_account.key();
         ctx.accounts.collateral_account.token_account = ctx.accounts.token_account.key();
         ctx.accounts.collateral_account.bump = ctx.bumps.collateral_account;
@@ -93,34 +147,95 @@ pub fn process_deposit_collateral_and_mint_tokens(
         msg!("New collateral account initialized for {}", ctx.accounts.depositer.key());
     }
     
-    ctx.accounts.collateral_account.lamport_balance = new_lamport_balance;
-    ctx.accounts.collateral_account.amount_minted += amount_to_mint;
-
-    check_health_factor(
-        amount_collateral,
+    // SOL can only go into an e-mode vault whose category includes it
+    let emode = load_emode_category(
         &ctx.accounts.collateral_account,
-        &ctx.accounts.config_account,
-        &ctx.accounts.price_update,
+        ctx.accounts.emode_category.as_deref().map(|category| &**category),
     )?;
-
-    let collateral_value_in_usd = crate::get_usd_value(
-        new_lamport_balance, 
-        &ctx.accounts.price_update
-    )?;
-    
-    let collateralization_ratio = (collateral_value_in_usd * 100) / ctx.accounts.collateral_account.amount_minted;
-    
     require!(
-        collateralization_ratio >= MIN_COLLATERAL_RATIO,
-        CustomError::InsufficientCollateralization
+        amount_collateral == 0 || emode.is_none() || sol_emode(emode).is_some(),
+        CustomError::EModeCategoryMismatch
     );
     
-    if collateralization_ratio > MAX_COLLATERAL_RATIO {
-        msg!(
-            "Warning: High collateralization ratio {}% exceeds recommended maximum {}%",
-            collateralization_ratio,
-            MAX_COLLATERAL_RATIO
+    ctx.accounts.collateral_account.lamport_balance = new_lamport_balance;
+    ctx.accounts.collateral_account.amount_minted = ctx.accounts.collateral_account.amount_minted
+        .checked_add(amount_to_mint)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    ctx.accounts.config_account.vault_debt = ctx.accounts.config_account.vault_debt
+        .checked_add(amount_to_mint)
+        .ok_or(error!(CustomError::MathOverflow))?;
+
+    // Minting adds risk, a plain top-up doesn't
+    let risk_profile = RiskProfile::for_position_change(
+        amount_to_mint,
+        0,
+        ctx.accounts.collateral_account.amount_minted,
+    );
+    let price = load_price_for_operation(
+        risk_profile,
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &mut ctx.accounts.collateral_account,
+        ctx.accounts.price_update.as_ref().map(|account| account.as_ref()),
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+    )?;
+
+    // A top-up can only make the position safer, so I only judge positions that mint
+    if let Some(price) = price.filter(|_| risk_profile.increases_risk()) {
+        // Every token in the vault counts towards the position at its own price
+        let asset_prices = load_asset_prices(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.oracle_config,
+            ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+            ctx.remaining_accounts,
+        )?;
+
+        check_health_factor(
+            &value_collateral(
+                &ctx.accounts.collateral_account,
+                &ctx.accounts.config_account,
+                emode,
+                amount_collateral,
+                &price,
+                &asset_prices,
+            )?,
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.config_account,
+        )?;
+
+        let collateral_value_in_usd = value_collateral(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.config_account,
+            emode,
+            new_lamport_balance,
+            &price,
+            &asset_prices,
+        )?
+        .total_value;
+        
+        let collateralization_ratio = calculate_collateralization_ratio(
+            collateral_value_in_usd,
+            ctx.accounts.collateral_account.amount_minted,
+        )?;
+        
+        // A vault in e-mode only has to meet its category's minimum ratio, and large vaults need more
+        let min_collateral_ratio = get_min_collateral_ratio(
+            &ctx.accounts.config_account,
+            emode,
+            ctx.accounts.collateral_account.amount_minted,
+        )?;
+        require!(
+            collateralization_ratio >= Decimal::from_percent(min_collateral_ratio),
+            CustomError::InsufficientCollateralization
         );
+        
+        if collateralization_ratio > Decimal::from_percent(MAX_COLLATERAL_RATIO) {
+            msg!(
+                "Warning: High collateralization ratio {} exceeds recommended maximum {}%",
+                collateralization_ratio,
+                MAX_COLLATERAL_RATIO
+            );
+        }
     }
 
     deposit_sol(
@@ -135,15 +250,21 @@ pub fn process_deposit_collateral_and_mint_tokens(
         &ctx.accounts.token_account,
         &ctx.accounts.token_program,
         adjusted_mint_amount,
+        &ctx.accounts.config_account.key(),
         ctx.accounts.config_account.bump_mint_account,
     )?;
-    
-    if let Some(fee_recipient_account) = &ctx.accounts.fee_recipient {
-        msg!(
-            "Protocol fee of {} tokens collected to {}",
+
+    // The vault owes the full amount, so the fee is minted too rather than left as debt nobody holds
+    if mint_fee > 0 {
+        mint_tokens(
+            &ctx.accounts.mint_account,
+            &ctx.accounts.treasury,
+            &ctx.accounts.token_program,
             mint_fee,
-            fee_recipient_account.key()
-        );
+            &ctx.accounts.config_account.key(),
+            ctx.accounts.config_account.bump_mint_account,
+        )?;
+        msg!("Protocol fee of {} tokens minted to the treasury {}", mint_fee, ctx.accounts.treasury.key());
     }
     
     msg!(
@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use solana_stake_interface::state::StakeStateV2;
+use anchor_spl::stake::Stake;
+use crate::{
+    Collateral, Config, CustomError, EModeCategory, SEED_CONFIG_ACCOUNT, SEED_COLLATERAL_ACCOUNT, SEED_EMODE_CATEGORY, SEED_SOL_ACCOUNT,
+    deposit_stake, load_emode_category, read_stake_state, sol_emode,
+};
+
+#[derive(Accounts)]
+pub struct DepositStakeCollateral<'info> {
+    #[account(mut)]
+    pub depositer: Signer<'info>,
+
+    // The market this vault belongs to
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump,
+        has_one = sol_account,
+    )]
+    pub collateral_account: Account<'info, Collateral>,
+
+    // Only needed when the vault has opted into an e-mode category
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[collateral_account.emode_category]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+
+    // The vault's SOL account becomes the stake and withdraw authority
+    #[account(
+        seeds = [SEED_SOL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump_sol_account,
+    )]
+    pub sol_account: SystemAccount<'info>,
+
+    /// CHECK: I parse this as a native stake account and check its authorities myself
+    #[account(mut)]
+    pub stake_account: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, Stake>,
+}
+
+pub fn process_deposit_stake_collateral(ctx: Context<DepositStakeCollateral>) -> Result<()> {
+    require_keys_eq!(
+        ctx.accounts.collateral_account.stake_account,
+        Pubkey::default(),
+        CustomError::StakeAccountAlreadyDeposited
+    );
+
+    // Stake can only go into an e-mode vault whose category includes SOL
+    let emode = load_emode_category(
+        &ctx.accounts.collateral_account,
+        ctx.accounts.emode_category.as_deref().map(|category| &**category),
+    )?;
+    require!(
+        emode.is_none() || sol_emode(emode).is_some(),
+        CustomError::EModeCategoryMismatch
+    );
+
+    // I only take stake that's delegated and not on its way out
+    let StakeStateV2::Stake(meta, stake, _) = read_stake_state(&ctx.accounts.stake_account)? else {
+        return err!(CustomError::InvalidStakeAccount);
+    };
+    require!(
+        stake.delegation.deactivation_epoch == u64::MAX,
+        CustomError::InvalidStakeAccount
+    );
+
+    // The depositor has to control the stake outright, with no lockup a custodian could lean on
+    let depositer = ctx.accounts.depositer.key();
+    require!(
+        meta.authorized.staker == depositer && meta.authorized.withdrawer == depositer,
+        CustomError::InvalidStakeAccount
+    );
+    require!(
+        !meta.lockup.is_in_force(&ctx.accounts.clock, None),
+        CustomError::InvalidStakeAccount
+    );
+
+    deposit_stake(
+        &ctx.accounts.stake_account,
+        &ctx.accounts.depositer,
+        &ctx.accounts.sol_account,
+        &ctx.accounts.clock,
+        &ctx.accounts.stake_program,
+    )?;
+
+    // A top-up can only make the position safer, so it doesn't need a price
+    let collateral_account = &mut ctx.accounts.collateral_account;
+    collateral_account.stake_account = ctx.accounts.stake_account.key();
+    collateral_account.stake_lamports = stake.delegation.stake;
+
+    msg!(
+        "Stake deposit successful: {} delegated lamports from {} to validator {}",
+        stake.delegation.stake,
+        ctx.accounts.stake_account.key(),
+        stake.delegation.voter_pubkey
+    );
+
+    Ok(())
+}
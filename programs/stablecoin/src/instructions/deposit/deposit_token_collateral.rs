@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface}};
+use crate::{
+    Collateral, CollateralConfig, Config, CustomError,
+    SEED_CONFIG_ACCOUNT, SEED_COLLATERAL_ACCOUNT, SEED_COLLATERAL_CONFIG, SEED_SOL_ACCOUNT, NO_EMODE_CATEGORY,
+    add_to_position, deposit_token,
+};
+
+#[derive(Accounts)]
+pub struct DepositTokenCollateral<'info> {
+    #[account(mut)]
+    pub depositer: Signer<'info>,
+
+    // The market this vault belongs to
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    // The token has to be listed in my collateral registry
+    #[account(
+        seeds = [SEED_COLLATERAL_CONFIG, config_account.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump,
+        has_one = sol_account,
+    )]
+    pub collateral_account: Account<'info, Collateral>,
+
+    // The vault's SOL account also owns its collateral token accounts
+    #[account(
+        seeds = [SEED_SOL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump_sol_account,
+    )]
+    pub sol_account: SystemAccount<'info>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = collateral_mint,
+        token::authority = depositer,
+        token::token_program = collateral_token_program,
+    )]
+    pub depositer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = depositer,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = sol_account,
+        associated_token::token_program = collateral_token_program,
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn process_deposit_token_collateral(ctx: Context<DepositTokenCollateral>, amount: u64) -> Result<()> {
+    let collateral_mint = ctx.accounts.collateral_mint.key();
+
+    // An e-mode vault only takes tokens from its own category
+    let emode_category = ctx.accounts.collateral_account.emode_category;
+    require!(
+        emode_category == NO_EMODE_CATEGORY || ctx.accounts.collateral_config.emode_category == emode_category,
+        CustomError::EModeCategoryMismatch
+    );
+
+    // A top-up can only make the position safer, so it doesn't need a price
+    add_to_position(&mut ctx.accounts.collateral_account, collateral_mint, amount)?;
+
+    deposit_token(
+        &ctx.accounts.depositer_token_account,
+        &ctx.accounts.collateral_vault,
+        &ctx.accounts.collateral_mint,
+        &ctx.accounts.depositer,
+        &ctx.accounts.collateral_token_program,
+        amount,
+    )?;
+
+    msg!(
+        "Token deposit successful: {} of {} deposited, {} collateral tokens held",
+        amount,
+        collateral_mint,
+        ctx.accounts.collateral_account.positions.len()
+    );
+
+    Ok(())
+}
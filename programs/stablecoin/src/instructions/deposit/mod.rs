@@ -1,6 +1,10 @@
 pub use deposit_collateral_and_mint_tokens::*;
+pub use deposit_token_collateral::*;
+pub use deposit_stake_collateral::*;
 pub use utils::*;
 
 
 pub mod deposit_collateral_and_mint_tokens;
+pub mod deposit_token_collateral;
+pub mod deposit_stake_collateral;
 pub mod utils;
\ No newline at end of file
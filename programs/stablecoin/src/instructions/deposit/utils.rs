@@ -1,17 +1,26 @@
-use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
-use anchor_spl::{token_2022::{mint_to, MintTo, Token2022}, token_interface::{Mint, TokenAccount}};
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    token_2022::{mint_to, MintTo, Token2022},
+    stake::{authorize, Authorize, Stake},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+use solana_stake_interface::state::StakeAuthorize;
 
-use crate::SEED_MINT_ACCOUNT;
+use crate::{SEED_GOVERNANCE_MINT, SEED_MINT_ACCOUNT};
 
 pub fn mint_tokens<'info>(
     mint_account: &InterfaceAccount<'info, Mint>,
     token_account: &InterfaceAccount<'info, TokenAccount>,
     token_program: &Program<'info, Token2022>,
     amount: u64,
+    market: &Pubkey,
     bump: u8,
 ) -> Result<()> { 
     // I need to create the PDA signer seeds to authorize minting
-    let signer_seeds: &[&[&[u8]]] = &[&[SEED_MINT_ACCOUNT, &[bump]]];
+    let signer_seeds: &[&[&[u8]]] = &[&[SEED_MINT_ACCOUNT, market.as_ref(), &[bump]]];
 
     // I mint the tokens to the user's token account using the mint authority
     mint_to(
@@ -29,6 +38,32 @@ pub fn mint_tokens<'info>(
     Ok(())
 }
 
+pub fn mint_governance_tokens<'info>(
+    governance_mint: &InterfaceAccount<'info, Mint>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Program<'info, Token2022>,
+    amount: u64,
+    market: &Pubkey,
+    bump: u8,
+) -> Result<()> {
+    // The governance mint is its own authority, just like the stablecoin mint
+    let signer_seeds: &[&[&[u8]]] = &[&[SEED_GOVERNANCE_MINT, market.as_ref(), &[bump]]];
+
+    mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: governance_mint.to_account_info(),
+                to: token_account.to_account_info(),
+                authority: governance_mint.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+    )?;
+    Ok(())
+}
+
 pub fn deposit_sol<'info>(
     from: &Signer<'info>,
     to: &SystemAccount<'info>,
@@ -47,5 +82,58 @@ pub fn deposit_sol<'info>(
         amount,
     )?;
     
+    Ok(())
+}
+
+pub fn deposit_token<'info>(
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    // I move the user's collateral tokens into their vault
+    transfer_checked(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+        ),
+        amount,
+        mint.decimals,
+    )?;
+
+    Ok(())
+}
+
+pub fn deposit_stake<'info>(
+    stake_account: &AccountInfo<'info>,
+    depositer: &Signer<'info>,
+    sol_account: &SystemAccount<'info>,
+    clock: &Sysvar<'info, Clock>,
+    stake_program: &Program<'info, Stake>,
+) -> Result<()> {
+    // I hand both stake authorities over to the vault, so only I can move the stake from now on
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        authorize(
+            CpiContext::new(
+                stake_program.to_account_info(),
+                Authorize {
+                    stake: stake_account.to_account_info(),
+                    authorized: depositer.to_account_info(),
+                    new_authorized: sol_account.to_account_info(),
+                    clock: clock.to_account_info(),
+                },
+            ),
+            stake_authorize,
+            None,
+        )?;
+    }
+
     Ok(())
 }
\ No newline at end of file
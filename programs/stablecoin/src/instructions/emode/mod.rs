@@ -0,0 +1,3 @@
+pub use set_emode_category::*;
+
+pub mod set_emode_category;
@@ -0,0 +1,124 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Collateral, Config, CustomError, EModeCategory, OracleConfig, OracleState,
+    SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, SEED_EMODE_CATEGORY, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE,
+    SEED_SOL_ACCOUNT, NO_EMODE_CATEGORY,
+    check_emode_membership, check_health_factor, load_asset_prices, load_position_configs, load_price_for_operation,
+    value_collateral, RiskProfile,
+};
+
+#[derive(Accounts)]
+#[instruction(category_id: u8)]
+pub struct SetEModeCategory<'info> {
+    #[account(mut)]
+    pub depositer: Signer<'info>,
+
+    // I need the price feed to re-check the health factor of a vault with debt
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only needed in markets that aren't denominated in USD
+    /// CHECK: I validate this against the currency feed pinned in the oracle config
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Box<Account<'info, OracleConfig>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Box<Account<'info, OracleState>>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump,
+        has_one = sol_account,
+    )]
+    pub collateral_account: Account<'info, Collateral>,
+
+    #[account(
+        seeds = [SEED_SOL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump_sol_account,
+    )]
+    pub sol_account: SystemAccount<'info>,
+
+    // The category the vault is moving into - not needed when it leaves e-mode
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[category_id]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+}
+
+pub fn process_set_emode_category(ctx: Context<SetEModeCategory>, category_id: u8) -> Result<()> {
+    // Every token the vault holds has to belong to the new category
+    let emode = if category_id == NO_EMODE_CATEGORY {
+        None
+    } else {
+        let category = ctx.accounts.emode_category
+            .as_deref()
+            .ok_or(error!(CustomError::MissingEModeCategory))?;
+        check_emode_membership(
+            &ctx.accounts.collateral_account,
+            ctx.accounts.sol_account.lamports(),
+            category,
+            &load_position_configs(&ctx.accounts.collateral_account, ctx.remaining_accounts)?,
+        )?;
+        Some(category)
+    };
+    ctx.accounts.collateral_account.emode_category = category_id;
+
+    // Switching terms under outstanding debt can make a position riskier, so I judge it again
+    let risk_profile = if ctx.accounts.collateral_account.amount_minted > 0 {
+        RiskProfile::Increasing
+    } else {
+        RiskProfile::Reducing
+    };
+    let price = load_price_for_operation(
+        risk_profile,
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &mut ctx.accounts.collateral_account,
+        ctx.accounts.price_update.as_ref().map(|account| account.as_ref()),
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+    )?;
+
+    if let Some(price) = price.filter(|_| risk_profile.increases_risk()) {
+        let asset_prices = load_asset_prices(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.oracle_config,
+            ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+            ctx.remaining_accounts,
+        )?;
+        let valuation = value_collateral(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.config_account,
+            emode.map(|category| &**category),
+            ctx.accounts.sol_account.lamports(),
+            &price,
+            &asset_prices,
+        )?;
+
+        check_health_factor(&valuation, &ctx.accounts.collateral_account, &ctx.accounts.config_account)?;
+    }
+
+    msg!(
+        "Vault {} switched to e-mode category {}",
+        ctx.accounts.collateral_account.depositer,
+        category_id
+    );
+
+    Ok(())
+}
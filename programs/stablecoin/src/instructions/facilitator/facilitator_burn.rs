@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, Token2022, TokenAccount};
+use crate::{Config, CustomError, Facilitator, SEED_CONFIG_ACCOUNT, SEED_FACILITATOR, burn_tokens};
+
+#[derive(Accounts)]
+pub struct FacilitatorBurn<'info> {
+    // The facilitator program's PDA, which only that program can sign for through CPI
+    pub facilitator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = mint_account,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_FACILITATOR, config_account.key().as_ref(), facilitator_account.program_id.as_ref()],
+        bump = facilitator_account.bump,
+        has_one = facilitator,
+    )]
+    pub facilitator_account: Account<'info, Facilitator>,
+
+    #[account(mut)]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    // The facilitator burns from its own token account
+    #[account(
+        mut,
+        token::mint = mint_account,
+        token::authority = facilitator,
+        token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+pub fn process_facilitator_burn(ctx: Context<FacilitatorBurn>, amount: u64) -> Result<()> {
+    // Burning still works after governance lowers the capacity, so a facilitator can always wind down
+    let facilitator_account = &mut ctx.accounts.facilitator_account;
+    facilitator_account.bucket_level = facilitator_account.bucket_level
+        .checked_sub(amount)
+        .ok_or(error!(CustomError::FacilitatorBurnExceedsLevel))?;
+
+    ctx.accounts.config_account.facilitator_debt = ctx.accounts.config_account.facilitator_debt
+        .checked_sub(amount)
+        .ok_or(error!(CustomError::MathOverflow))?;
+
+    burn_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.mint_account,
+        &ctx.accounts.token_account,
+        &ctx.accounts.facilitator,
+        amount,
+    )?;
+
+    msg!(
+        "Facilitator {} burned {}, bucket {}/{}, market debt {}",
+        ctx.accounts.facilitator.key(),
+        amount,
+        ctx.accounts.facilitator_account.bucket_level,
+        ctx.accounts.facilitator_account.bucket_capacity,
+        ctx.accounts.config_account.total_debt()?
+    );
+
+    Ok(())
+}
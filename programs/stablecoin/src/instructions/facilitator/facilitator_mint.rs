@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, Token2022, TokenAccount};
+use crate::{Config, CustomError, Facilitator, SEED_CONFIG_ACCOUNT, SEED_FACILITATOR, mint_tokens};
+
+#[derive(Accounts)]
+pub struct FacilitatorMint<'info> {
+    // The facilitator program's PDA, which only that program can sign for through CPI
+    pub facilitator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+        has_one = mint_account,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    // Only facilitators governance has registered in this market have a bucket
+    #[account(
+        mut,
+        seeds = [SEED_FACILITATOR, config_account.key().as_ref(), facilitator_account.program_id.as_ref()],
+        bump = facilitator_account.bump,
+        has_one = facilitator,
+    )]
+    pub facilitator_account: Account<'info, Facilitator>,
+
+    #[account(mut)]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    // The facilitator decides where the new stablecoins go
+    #[account(
+        mut,
+        token::mint = mint_account,
+        token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+pub fn process_facilitator_mint(ctx: Context<FacilitatorMint>, amount: u64) -> Result<()> {
+    require!(!ctx.accounts.config_account.emergency_shutdown, CustomError::EmergencyShutdown);
+
+    // A facilitator's debt is backed by whatever it holds off this program, so its bucket is the only limit
+    let facilitator_account = &mut ctx.accounts.facilitator_account;
+    let bucket_level = facilitator_account.bucket_level
+        .checked_add(amount)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    require!(
+        bucket_level <= facilitator_account.bucket_capacity,
+        CustomError::FacilitatorCapacityExceeded
+    );
+    facilitator_account.bucket_level = bucket_level;
+
+    ctx.accounts.config_account.facilitator_debt = ctx.accounts.config_account.facilitator_debt
+        .checked_add(amount)
+        .ok_or(error!(CustomError::MathOverflow))?;
+
+    mint_tokens(
+        &ctx.accounts.mint_account,
+        &ctx.accounts.token_account,
+        &ctx.accounts.token_program,
+        amount,
+        &ctx.accounts.config_account.key(),
+        ctx.accounts.config_account.bump_mint_account,
+    )?;
+
+    msg!(
+        "Facilitator {} minted {}, bucket {}/{}, market debt {}",
+        ctx.accounts.facilitator.key(),
+        amount,
+        ctx.accounts.facilitator_account.bucket_level,
+        ctx.accounts.facilitator_account.bucket_capacity,
+        ctx.accounts.config_account.total_debt()?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub mod facilitator_mint;
+pub mod facilitator_burn;
+
+pub use facilitator_mint::*;
+pub use facilitator_burn::*;
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, GovernanceConfig, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, CustomError,
+    instructions::governance::create_proposal::{Proposal, ProposalStatus}
+};
+
+#[derive(Accounts)]
+#[instruction(proposal_id: u64)]
+pub struct CancelProposal<'info> {
+    // Either the proposer or the guardian
+    pub canceller: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        seeds = [SEED_GOVERNANCE, config.key().as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+pub fn process_cancel_proposal(
+    ctx: Context<CancelProposal>,
+    _proposal_id: u64,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    let canceller = ctx.accounts.canceller.key();
+    let guardian = ctx.accounts.governance_config.guardian;
+    let proposal = &mut ctx.accounts.proposal;
+    
+    // A proposer can change their mind while voting is still open
+    let proposer_can_cancel = canceller == proposal.proposer
+        && proposal.status == ProposalStatus::Active
+        && current_time <= proposal.voting_ends_at;
+    
+    // The guardian can stop anything that hasn't executed yet, including passed proposals sitting in the timelock
+    let guardian_can_cancel = guardian != Pubkey::default()
+        && canceller == guardian
+        && matches!(proposal.status, ProposalStatus::Active | ProposalStatus::Passed);
+    
+    require!(proposer_can_cancel || guardian_can_cancel, CustomError::CannotCancelProposal);
+    
+    proposal.status = ProposalStatus::Cancelled;
+    
+    msg!("Proposal {} cancelled by {}", proposal.id, canceller);
+    
+    Ok(())
+}
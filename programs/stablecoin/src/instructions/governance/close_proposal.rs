@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, CustomError,
+    instructions::governance::create_proposal::Proposal
+};
+
+#[derive(Accounts)]
+#[instruction(proposal_id: u64)]
+pub struct CloseProposal<'info> {
+    // The proposer paid the rent, so they get it back
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        constraint = proposal.is_finished(Clock::get()?.unix_timestamp) @ CustomError::ProposalNotFinished,
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+pub fn process_close_proposal(
+    ctx: Context<CloseProposal>,
+    _proposal_id: u64,
+) -> Result<()> {
+    msg!(
+        "Proposal {} closed by {} ({:?})",
+        ctx.accounts.proposal.id,
+        ctx.accounts.proposer.key(),
+        ctx.accounts.proposal.status
+    );
+    
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{TokenAccount, Token2022};
+use anchor_spl::token_interface::Mint;
 use crate::{
-    Config, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, 
-    MIN_PROPOSAL_THRESHOLD, VOTING_PERIOD, CustomError
+    Config, GovernanceConfig, TierThresholds, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, SEED_VOTER_WEIGHT,
+    BPS_DIVISOR, SEED_GOVERNANCE_AUTHORITY, MAX_GOVERNANCE_INSTRUCTIONS, CustomError, mul_div_ceil,
+    instructions::governance::deposit_governance_tokens::VoterWeight,
 };
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
@@ -12,10 +13,118 @@ pub enum ProposalType {
     UpdateLiquidationBonus(u64),
     UpdateOracleConfig(u64),
     UpdateFeeStructure { mint_fee: u16, burn_fee: u16, liquidation_fee: u16 },
+    UpdateFixedPrice { price: i64, exponent: i32 },
+    ResetCircuitBreaker,
+    UpdateEModeCategory {
+        id: u8,
+        includes_sol: bool,
+        liquidation_threshold: u64,
+        liquidation_bonus: u64,
+        min_collateral_ratio: u64,
+    },
+    SetCollateralEModeCategory { mint: Pubkey, category: u8 },
+    SetDebtTier {
+        index: u8,
+        min_debt: u64,
+        threshold_haircut_bps: u64,
+        extra_collateral_ratio: u64,
+    },
+    SetFacilitator { program_id: Pubkey, bucket_capacity: u64 },
+    UpdateMaxLockDuration(i64),
+    EmergencyShutdown,
+    MintGovernanceTokens { recipient: Pubkey, amount: u64 },
+    UpdateGovernanceConfig {
+        voting_period: i64,
+        execution_delay: i64,
+        execution_window: i64,
+        proposal_threshold: u64,
+    },
+    SetGuardian(Pubkey),
+    UpdateProposalThresholds { tier: ProposalTier, quorum_bps: u64, approval_bps: u64 },
+    ExecuteInstructions(Vec<GovernanceInstruction>),
+}
+
+// An account an instruction run by governance reads or writes
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct GovernanceAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+// An arbitrary instruction, stored on the proposal and invoked once the timelock has passed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct GovernanceInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<GovernanceAccountMeta>,
+    pub data: Vec<u8>,
+}
+
+impl GovernanceInstruction {
+    // Program id, account list and data, as borsh lays them out
+    pub fn serialized_len(&self) -> usize {
+        32 + 4 + self.accounts.len() * (32 + 1 + 1) + 4 + self.data.len()
+    }
+}
+
+// How much damage a proposal could do, which sets the bar it has to clear
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ProposalTier {
+    Standard,
+    Risk,
+    Critical,
+}
+
+impl ProposalTier {
+    pub fn thresholds(self, governance_config: &GovernanceConfig) -> TierThresholds {
+        match self {
+            ProposalTier::Standard => governance_config.standard,
+            ProposalTier::Risk => governance_config.risk,
+            ProposalTier::Critical => governance_config.critical,
+        }
+    }
+}
+
+impl ProposalType {
+    // Anything that moves risk parameters or minting capacity needs a higher bar than housekeeping
+    pub fn tier(&self) -> ProposalTier {
+        // I list every type, so a new one has to be given a tier rather than falling into one
+        match self {
+            ProposalType::UpdateOracleConfig(_) | ProposalType::UpdateFeeStructure { .. } => ProposalTier::Standard,
+            ProposalType::UpdateMinHealthFactor(_)
+            | ProposalType::UpdateLiquidationThreshold(_)
+            | ProposalType::UpdateLiquidationBonus(_)
+            | ProposalType::UpdateFixedPrice { .. }
+            | ProposalType::ResetCircuitBreaker
+            | ProposalType::UpdateEModeCategory { .. }
+            | ProposalType::SetCollateralEModeCategory { .. }
+            | ProposalType::SetDebtTier { .. }
+            | ProposalType::UpdateMaxLockDuration(_) => ProposalTier::Risk,
+            // A facilitator mints against nothing held here, up to whatever capacity governance gives it
+            ProposalType::SetFacilitator { .. }
+            | ProposalType::EmergencyShutdown
+            | ProposalType::MintGovernanceTokens { .. }
+            | ProposalType::UpdateGovernanceConfig { .. }
+            | ProposalType::UpdateProposalThresholds { .. }
+            | ProposalType::SetGuardian(_)
+            | ProposalType::ExecuteInstructions(_) => ProposalTier::Critical,
+        }
+    }
+
+    // Room needed beyond the fixed INIT_SPACE, since instruction lists can be any size
+    pub fn extra_space(&self) -> usize {
+        match self {
+            ProposalType::ExecuteInstructions(instructions) => {
+                let len = 1 + 4 + instructions.iter().map(GovernanceInstruction::serialized_len).sum::<usize>();
+                len.saturating_sub(ProposalType::INIT_SPACE)
+            },
+            _ => 0,
+        }
+    }
 }
 
 impl anchor_lang::Space for ProposalType {
-    const INIT_SPACE: usize = 32;
+    const INIT_SPACE: usize = 64;
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
@@ -35,18 +144,43 @@ impl anchor_lang::Space for ProposalStatus {
 #[derive(InitSpace)]
 pub struct Proposal {
     pub id: u64,
+    // Unique across every proposal in the market, unlike the id, so votes on a closed proposal can be told apart
+    pub nonce: u64,
     pub proposer: Pubkey,
     pub proposal_type: ProposalType,
     #[max_len(200)]
     pub description: String,
     pub votes_for: u64,
     pub votes_against: u64,
+    // The locked balance behind every vote cast, in the same units as quorum_votes
+    pub quorum_balance: u64,
     pub created_at: i64,
+    // Every vote is weighed by the voter's deposited balance at this slot
+    pub snapshot_slot: u64,
     pub voting_ends_at: i64,
+    // The locked balance that has to vote for quorum, out of the governance supply when the proposal was created
+    pub quorum_votes: u64,
+    // The share of votes that have to be in favour, in basis points
+    pub approval_bps: u64,
+    pub finalized_at: i64,
+    // A passed proposal can be executed from here until it expires, both fixed when it's finalized
+    pub executable_at: i64,
+    pub expires_at: i64,
     pub status: ProposalStatus,
     pub bump: u8,
 }
 
+impl Proposal {
+    // Nothing more can happen to a proposal once it's executed, rejected, cancelled or expired
+    pub fn is_finished(&self, current_time: i64) -> bool {
+        match self.status {
+            ProposalStatus::Executed | ProposalStatus::Rejected | ProposalStatus::Cancelled => true,
+            ProposalStatus::Passed => current_time > self.expires_at,
+            ProposalStatus::Active => false,
+        }
+    }
+}
+
 #[derive(Accounts)]
 #[instruction(proposal_id: u64, description: String, proposal_type: ProposalType)]
 pub struct CreateProposal<'info> {
@@ -54,28 +188,38 @@ pub struct CreateProposal<'info> {
     pub proposer: Signer<'info>,
     
     #[account(
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
         bump = config.bump,
+        has_one = governance_mint,
     )]
     pub config: Account<'info, Config>,
     
     #[account(
         mut,
-        constraint = proposer_token_account.amount >= MIN_PROPOSAL_THRESHOLD @ CustomError::InsufficientGovernanceBalance,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref()],
+        bump = governance_config.bump,
     )]
-    pub proposer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub governance_config: Account<'info, GovernanceConfig>,
+    
+    // I read the governance supply from here when the snapshot is taken
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), proposer.key().as_ref()],
+        bump = proposer_weight.bump,
+    )]
+    pub proposer_weight: Account<'info, VoterWeight>,
     
     #[account(
         init,
         payer = proposer,
-        space = 8 + Proposal::INIT_SPACE,
-        seeds = [SEED_GOVERNANCE, proposal_id.to_le_bytes().as_ref()],
+        space = 8 + Proposal::INIT_SPACE + proposal_type.extra_space(),
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
         bump,
     )]
     pub proposal: Account<'info, Proposal>,
     
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token2022>,
 }
 
 pub fn process_create_proposal(
@@ -88,23 +232,67 @@ pub fn process_create_proposal(
     let clock = Clock::get()?;
     let current_time = clock.unix_timestamp;
     
+    // Proposers need enough locked voting power right now
+    let proposer_power = ctx.accounts.proposer_weight
+        .current_lock()
+        .voting_power(current_time, ctx.accounts.config.max_lock_duration)?;
+    require!(
+        proposer_power >= ctx.accounts.governance_config.proposal_threshold,
+        CustomError::InsufficientGovernanceBalance
+    );
+    
+    // Instruction lists have to fit in the executing transaction, and only governance can sign for them
+    if let ProposalType::ExecuteInstructions(instructions) = &proposal_type {
+        require!(
+            !instructions.is_empty() && instructions.len() <= MAX_GOVERNANCE_INSTRUCTIONS,
+            CustomError::InvalidGovernanceInstructions
+        );
+        let (governance_authority, _) = Pubkey::find_program_address(
+            &[SEED_GOVERNANCE_AUTHORITY, ctx.accounts.config.key().as_ref()],
+            &crate::ID,
+        );
+        for instruction in instructions {
+            require!(
+                instruction.accounts.iter().all(|meta| !meta.is_signer || meta.pubkey == governance_authority),
+                CustomError::InvalidGovernanceInstructions
+            );
+        }
+    }
+    
     // I calculate when voting will end based on my configured voting period
-    let voting_ends_at = current_time + VOTING_PERIOD as i64;
+    let voting_ends_at = current_time + ctx.accounts.governance_config.voting_period;
+    
+    // I number proposals from 1, so a vote record that was just created never matches one
+    let governance_config = &mut ctx.accounts.governance_config;
+    governance_config.proposal_count = governance_config.proposal_count
+        .checked_add(1)
+        .ok_or(error!(CustomError::MathOverflow))?;
     
     // I set up all the proposal details to track it through the governance process
     let proposal = &mut ctx.accounts.proposal;
     proposal.id = proposal_id;
+    proposal.nonce = governance_config.proposal_count;
     proposal.proposer = ctx.accounts.proposer.key();
+    let thresholds = proposal_type.tier().thresholds(governance_config);
+    proposal.quorum_votes = mul_div_ceil(ctx.accounts.governance_mint.supply, thresholds.quorum_bps, BPS_DIVISOR as u64)?;
+    proposal.approval_bps = thresholds.approval_bps;
     proposal.proposal_type = proposal_type;
     proposal.description = description;
     
     // Every proposal starts with zero votes
     proposal.votes_for = 0;
     proposal.votes_against = 0;
+    proposal.quorum_balance = 0;
     
     // I track timing for the proposal lifecycle
     proposal.created_at = current_time;
+    
+    // I snapshot the slot before this one, so balances deposited alongside the proposal don't count
+    proposal.snapshot_slot = clock.slot.saturating_sub(1);
     proposal.voting_ends_at = voting_ends_at;
+    proposal.finalized_at = 0;
+    proposal.executable_at = 0;
+    proposal.expires_at = 0;
     
     // All new proposals start in the Active status
     proposal.status = ProposalStatus::Active;
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_VOTER_WEIGHT, CustomError,
+    instructions::governance::deposit_governance_tokens::VoterWeight,
+};
+
+#[derive(Accounts)]
+pub struct Delegate<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), owner.key().as_ref()],
+        bump = voter_weight.bump,
+    )]
+    pub voter_weight: Account<'info, VoterWeight>,
+}
+
+// Hands the voter's power to a delegate for every proposal snapshotted from now on; the tokens stay locked here
+pub fn process_delegate(ctx: Context<Delegate>, delegate: Pubkey) -> Result<()> {
+    require!(
+        delegate != Pubkey::default() && delegate != ctx.accounts.owner.key(),
+        CustomError::InvalidDelegate
+    );
+    
+    ctx.accounts.voter_weight.record_delegate(Clock::get()?.slot, delegate);
+    
+    msg!("Voter {} delegated to {}", ctx.accounts.owner.key(), delegate);
+    
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface}};
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_VOTER_WEIGHT, MAX_VOTER_WEIGHT_CHECKPOINTS, CustomError,
+    deposit_token, mul_div_floor,
+};
+
+// A voter's locked balance, when it unlocks, and who votes with it, as of some slot
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, InitSpace)]
+pub struct Checkpoint {
+    pub slot: u64,
+    pub balance: u64,
+    pub unlock_at: i64,
+    // The default key means the voter hasn't delegated
+    pub delegate: Pubkey,
+}
+
+impl Checkpoint {
+    // Voting power decays linearly from the full balance at the longest lock down to nothing at unlock
+    pub fn voting_power(&self, timestamp: i64, max_lock_duration: i64) -> Result<u64> {
+        let remaining = self.unlock_at.saturating_sub(timestamp).clamp(0, max_lock_duration);
+        mul_div_floor(self.balance, remaining as u64, max_lock_duration as u64)
+    }
+
+    // Quorum is measured against the governance supply, so it counts the balance itself rather than its decayed
+    // power - but only while the lock is still running
+    pub fn quorum_balance(&self, timestamp: i64) -> u64 {
+        if self.unlock_at > timestamp { self.balance } else { 0 }
+    }
+}
+
+// Governance tokens a voter has locked, with their lock history so votes can read a past snapshot
+#[account]
+#[derive(InitSpace)]
+pub struct VoterWeight {
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    #[max_len(MAX_VOTER_WEIGHT_CHECKPOINTS)]
+    pub checkpoints: Vec<Checkpoint>,
+    pub bump: u8,
+}
+
+impl VoterWeight {
+    pub fn current_lock(&self) -> Checkpoint {
+        self.checkpoints.last().copied().unwrap_or_default()
+    }
+
+    // The lock as it stood at the end of the given slot; once old checkpoints are dropped I undercount, never overcount
+    pub fn lock_at(&self, slot: u64) -> Checkpoint {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.slot <= slot)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // A new balance or unlock time keeps the current delegate
+    pub fn record(&mut self, slot: u64, balance: u64, unlock_at: i64) {
+        let delegate = self.current_lock().delegate;
+        self.push(Checkpoint { slot, balance, unlock_at, delegate });
+    }
+
+    // A new delegate keeps the current lock
+    pub fn record_delegate(&mut self, slot: u64, delegate: Pubkey) {
+        let lock = self.current_lock();
+        self.push(Checkpoint { slot, delegate, ..lock });
+    }
+
+    fn push(&mut self, checkpoint: Checkpoint) {
+        match self.checkpoints.last_mut() {
+            Some(last) if last.slot == checkpoint.slot => *last = checkpoint,
+            _ => {
+                if self.checkpoints.len() == MAX_VOTER_WEIGHT_CHECKPOINTS {
+                    self.checkpoints.remove(0);
+                }
+                self.checkpoints.push(checkpoint);
+            },
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct DepositGovernanceTokens<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = governance_mint,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VoterWeight::INIT_SPACE,
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub voter_weight: Account<'info, VoterWeight>,
+    
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        token::mint = governance_mint,
+        token::authority = owner,
+        token::token_program = token_program,
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    // The voter weight account holds the deposited tokens itself
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = governance_mint,
+        associated_token::authority = voter_weight,
+        associated_token::token_program = token_program,
+    )]
+    pub voter_weight_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+// Adds to the voter's lock without moving its unlock time; a voter without a lock has to extend it to get any power
+pub fn process_deposit_governance_tokens(ctx: Context<DepositGovernanceTokens>, amount: u64) -> Result<()> {
+    deposit_token(
+        &ctx.accounts.owner_token_account,
+        &ctx.accounts.voter_weight_vault,
+        &ctx.accounts.governance_mint,
+        &ctx.accounts.owner,
+        &ctx.accounts.token_program,
+        amount,
+    )?;
+    
+    let voter_weight = &mut ctx.accounts.voter_weight;
+    voter_weight.market = ctx.accounts.config.key();
+    voter_weight.owner = ctx.accounts.owner.key();
+    voter_weight.bump = ctx.bumps.voter_weight;
+    
+    let lock = voter_weight.current_lock();
+    let balance = lock.balance
+        .checked_add(amount)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    voter_weight.record(Clock::get()?.slot, balance, lock.unlock_at);
+    
+    msg!(
+        "Voter {} locked {} more governance tokens, balance {} until {}",
+        ctx.accounts.owner.key(),
+        amount,
+        balance,
+        lock.unlock_at
+    );
+    
+    Ok(())
+}
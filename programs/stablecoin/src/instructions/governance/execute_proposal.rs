@@ -1,8 +1,15 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::{AccountMeta, Instruction}, program::invoke_signed};
+use anchor_spl::token_interface::{Mint, Token2022, TokenAccount};
 use crate::{
-    Config, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, CustomError, 
-    EXECUTION_DELAY,
-    instructions::governance::create_proposal::{Proposal, ProposalStatus, ProposalType}
+    CollateralConfig, Config, DebtTier, EModeCategory, Facilitator, GovernanceConfig, OracleConfig, OracleState,
+    SEED_CONFIG_ACCOUNT, SEED_EMODE_CATEGORY, SEED_FACILITATOR,
+    SEED_GOVERNANCE, SEED_GOVERNANCE_AUTHORITY, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE, CustomError, MIN_PRICE_EXPONENT,
+    MAX_PRICE_EXPONENT, NO_EMODE_CATEGORY, MAX_DEBT_TIERS, MAX_LOCK_DURATION_LIMIT, BPS_DIVISOR,
+    MIN_VOTING_PERIOD, MAX_VOTING_PERIOD, MIN_EXECUTION_DELAY, MAX_EXECUTION_DELAY, MIN_EXECUTION_WINDOW, MAX_EXECUTION_WINDOW,
+    clear_circuit_breaker, validate_collateral_params, validate_min_health_factor, validate_debt_tiers, mint_governance_tokens,
+    create_program_account,
+    instructions::governance::create_proposal::{GovernanceInstruction, Proposal, ProposalStatus, ProposalTier, ProposalType}
 };
 
 #[derive(Accounts)]
@@ -13,19 +20,64 @@ pub struct ExecuteProposal<'info> {
     
     #[account(
         mut,
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
         bump = config.bump,
     )]
     pub config: Account<'info, Config>,
     
     #[account(
         mut,
-        seeds = [SEED_GOVERNANCE, proposal_id.to_le_bytes().as_ref()],
+        seeds = [SEED_ORACLE_CONFIG, config.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Account<'info, OracleState>,
+    
+    // Governance can amend its own rules
+    #[account(
+        mut,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Box<Account<'info, GovernanceConfig>>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
         bump = proposal.bump,
         constraint = proposal.status == ProposalStatus::Passed @ CustomError::ProposalVotingEnded,
     )]
     pub proposal: Account<'info, Proposal>,
     
+    /// CHECK: Only needed for e-mode proposals - their handlers check it's the category's PDA, and
+    /// UpdateEModeCategory creates it the first time governance sets it
+    #[account(mut)]
+    pub emode_category: Option<UncheckedAccount<'info>>,
+    
+    // Only needed when a proposal moves a listed token into or out of an e-mode category
+    #[account(mut)]
+    pub collateral_config: Option<Box<Account<'info, CollateralConfig>>>,
+    
+    /// CHECK: Only needed for facilitator proposals - SetFacilitator checks it's the bucket's PDA and creates
+    /// it the first time governance sets it
+    #[account(mut)]
+    pub facilitator: Option<UncheckedAccount<'info>>,
+    
+    // Only needed when governance mints governance tokens
+    #[account(mut, address = config.governance_mint @ CustomError::InvalidParameter)]
+    pub governance_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    
+    #[account(mut, token::mint = config.governance_mint)]
+    pub recipient_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    
+    pub token_program: Option<Program<'info, Token2022>>,
+    
     pub system_program: Program<'info, System>,
 }
 
@@ -37,41 +89,34 @@ pub fn process_execute_proposal(
     let clock = Clock::get()?;
     let current_time = clock.unix_timestamp;
     
-    // I enforce a delay between voting end and execution to give users time to prepare
+    // I enforce a delay between finalization and execution to give users time to prepare
     require!(
-        current_time >= ctx.accounts.proposal.voting_ends_at + EXECUTION_DELAY as i64,
+        current_time >= ctx.accounts.proposal.executable_at,
         CustomError::ExecutionDelayNotSatisfied
     );
     
-    // I need to check if enough people voted for this proposal
-    let total_votes = ctx.accounts.proposal.votes_for + ctx.accounts.proposal.votes_against;
-    
-    // I require at least some votes to consider a proposal valid
-    require!(
-        total_votes > 0,
-        CustomError::QuorumNotReached
-    );
-    
-    // I only execute proposals that have more votes for than against
+    // A passed proposal nobody executed in time goes stale rather than waiting around to be used later
     require!(
-        ctx.accounts.proposal.votes_for > ctx.accounts.proposal.votes_against,
-        CustomError::ProposalVotingEnded
+        current_time <= ctx.accounts.proposal.expires_at,
+        CustomError::ProposalExpired
     );
     
     // I handle different types of proposals differently
-    match ctx.accounts.proposal.proposal_type {
+    match ctx.accounts.proposal.proposal_type.clone() {
         ProposalType::UpdateMinHealthFactor(new_value) => {
-            // I update the minimum health factor parameter
-            ctx.accounts.config.min_health_factor = new_value;
-            msg!("Updated min_health_factor to {}", new_value);
+            // I update the minimum health factor parameter (in basis points)
+            validate_min_health_factor(new_value)?;
+            ctx.accounts.config.min_health_factor_bps = new_value;
+            msg!("Updated min_health_factor_bps to {}", new_value);
         },
         ProposalType::UpdateLiquidationThreshold(new_value) => {
-            // I update the liquidation threshold parameter
+            // The base terms get the same checks as an e-mode category's
+            validate_collateral_params(new_value, ctx.accounts.config.liquidation_bonus)?;
             ctx.accounts.config.liquidation_threshold = new_value;
             msg!("Updated liquidation_threshold to {}", new_value);
         },
         ProposalType::UpdateLiquidationBonus(new_value) => {
-            // I update the liquidation bonus parameter
+            validate_collateral_params(ctx.accounts.config.liquidation_threshold, new_value)?;
             ctx.accounts.config.liquidation_bonus = new_value;
             msg!("Updated liquidation_bonus to {}", new_value);
         },
@@ -83,6 +128,249 @@ pub fn process_execute_proposal(
             // I'd update fee structure in a more complete implementation
             msg!("Fee structure updates not implemented in this version");
         },
+        ProposalType::UpdateFixedPrice { price, exponent } => {
+            // I update the governance-set price used for pegged collateral
+            require!(price > 0, CustomError::InvalidPrice);
+            require!(
+                (MIN_PRICE_EXPONENT..=MAX_PRICE_EXPONENT).contains(&exponent),
+                CustomError::UnsupportedPriceExponent
+            );
+            ctx.accounts.oracle_config.fixed_price = price;
+            ctx.accounts.oracle_config.fixed_exponent = exponent;
+            msg!("Updated fixed price to {} (exponent {})", price, exponent);
+        },
+        ProposalType::ResetCircuitBreaker => {
+            // I let the community clear the oracle circuit breaker too
+            clear_circuit_breaker(&mut ctx.accounts.oracle_state);
+            msg!("Oracle circuit breaker reset");
+        },
+        ProposalType::UpdateEModeCategory {
+            id,
+            includes_sol,
+            liquidation_threshold,
+            liquidation_bonus,
+            min_collateral_ratio,
+        } => {
+            // A category needs a real id, sane terms, and can't let a vault mint below 100% collateral
+            require!(id != NO_EMODE_CATEGORY, CustomError::InvalidParameter);
+            validate_collateral_params(liquidation_threshold, liquidation_bonus)?;
+            require!(min_collateral_ratio >= 100, CustomError::InvalidParameter);
+
+            let market = ctx.accounts.config.key();
+            let (emode_category, bump, _) = init_if_needed(
+                &ctx.accounts.emode_category,
+                &[SEED_EMODE_CATEGORY, market.as_ref(), &[id]],
+                8 + EModeCategory::INIT_SPACE,
+                CustomError::MissingEModeCategory,
+                &ctx.accounts.executor,
+                &ctx.accounts.system_program,
+            )?;
+            EModeCategory {
+                id,
+                includes_sol,
+                liquidation_threshold,
+                liquidation_bonus,
+                min_collateral_ratio,
+                bump,
+            }
+            .try_serialize(&mut &mut emode_category.try_borrow_mut_data()?[..])?;
+            msg!(
+                "Updated e-mode category {}: threshold={}%, bonus={}%, min ratio={}%, includes SOL={}",
+                id,
+                liquidation_threshold,
+                liquidation_bonus,
+                min_collateral_ratio,
+                includes_sol
+            );
+        },
+        ProposalType::SetCollateralEModeCategory { mint, category } => {
+            // I only move a token into a category governance has already set up
+            if category != NO_EMODE_CATEGORY {
+                let emode_category = ctx.accounts.emode_category
+                    .as_ref()
+                    .ok_or(error!(CustomError::MissingEModeCategory))?;
+                let (address, _) = Pubkey::find_program_address(
+                    &[SEED_EMODE_CATEGORY, ctx.accounts.config.key().as_ref(), &[category]],
+                    &crate::ID,
+                );
+                require!(
+                    emode_category.key() == address && emode_category.owner == &crate::ID,
+                    CustomError::MissingEModeCategory
+                );
+                let emode_category = EModeCategory::try_deserialize(&mut &emode_category.try_borrow_data()?[..])?;
+                require!(emode_category.id == category, CustomError::MissingEModeCategory);
+            }
+
+            let collateral_config = ctx.accounts.collateral_config
+                .as_mut()
+                .ok_or(error!(CustomError::MissingCollateralAccounts))?;
+            require_keys_eq!(collateral_config.market, ctx.accounts.config.key(), CustomError::MarketMismatch);
+            require_keys_eq!(collateral_config.mint, mint, CustomError::CollateralMintMismatch);
+            collateral_config.emode_category = category;
+            msg!("Moved collateral {} to e-mode category {}", mint, category);
+        },
+        ProposalType::SetDebtTier { index, min_debt, threshold_haircut_bps, extra_collateral_ratio } => {
+            // A tier can't wipe out collateral entirely or price vaults out of minting, and the table has to
+            // stay ordered so bigger vaults get stricter terms; setting min_debt to 0 switches a tier off
+            require!((index as usize) < MAX_DEBT_TIERS, CustomError::InvalidParameter);
+            let mut debt_tiers = ctx.accounts.config.debt_tiers;
+            debt_tiers[index as usize] = DebtTier {
+                min_debt,
+                threshold_haircut_bps,
+                extra_collateral_ratio,
+            };
+            validate_debt_tiers(&debt_tiers)?;
+            ctx.accounts.config.debt_tiers = debt_tiers;
+            msg!(
+                "Updated debt tier {}: min debt={}, threshold haircut={} bps, extra collateral ratio={}%",
+                index,
+                min_debt,
+                threshold_haircut_bps,
+                extra_collateral_ratio
+            );
+        },
+        ProposalType::SetFacilitator { program_id, bucket_capacity } => {
+            // Lowering the capacity below the level only stops new mints; the facilitator can still burn down
+            let market = ctx.accounts.config.key();
+            let (facilitator_info, bump, existed) = init_if_needed(
+                &ctx.accounts.facilitator,
+                &[SEED_FACILITATOR, market.as_ref(), program_id.as_ref()],
+                8 + Facilitator::INIT_SPACE,
+                CustomError::MissingFacilitatorAccount,
+                &ctx.accounts.executor,
+                &ctx.accounts.system_program,
+            )?;
+            let bucket_level = if existed {
+                Facilitator::try_deserialize(&mut &facilitator_info.try_borrow_data()?[..])?.bucket_level
+            } else {
+                0
+            };
+            let facilitator_account = Facilitator {
+                market,
+                program_id,
+                facilitator: Facilitator::signer(&program_id, &market),
+                bucket_capacity,
+                bucket_level,
+                bump,
+            };
+            facilitator_account.try_serialize(&mut &mut facilitator_info.try_borrow_mut_data()?[..])?;
+            msg!(
+                "Set facilitator {} bucket capacity to {} (level {})",
+                facilitator_account.facilitator,
+                bucket_capacity,
+                bucket_level
+            );
+        },
+        ProposalType::UpdateMaxLockDuration(max_lock_duration) => {
+            // Existing locks keep their unlock time; their weight is simply measured against the new maximum
+            require!(
+                max_lock_duration > 0 && max_lock_duration <= MAX_LOCK_DURATION_LIMIT,
+                CustomError::InvalidParameter
+            );
+            ctx.accounts.config.max_lock_duration = max_lock_duration;
+            msg!("Updated max lock duration to {}", max_lock_duration);
+        },
+        ProposalType::EmergencyShutdown => {
+            // There's no way back from a shutdown: minting stops for good and governance locks open up
+            ctx.accounts.config.emergency_shutdown = true;
+            msg!("Market {} is in emergency shutdown", ctx.accounts.config.market_id);
+        },
+        ProposalType::MintGovernanceTokens { recipient, amount } => {
+            // Governance decides every emission, to the exact token account named in the proposal
+            let (Some(governance_mint), Some(recipient_token_account), Some(token_program)) = (
+                &ctx.accounts.governance_mint,
+                &ctx.accounts.recipient_token_account,
+                &ctx.accounts.token_program,
+            ) else {
+                return err!(CustomError::MissingGovernanceMintAccounts);
+            };
+            require_keys_eq!(recipient_token_account.key(), recipient, CustomError::InvalidParameter);
+            mint_governance_tokens(
+                governance_mint,
+                recipient_token_account,
+                token_program,
+                amount,
+                &ctx.accounts.config.key(),
+                ctx.accounts.config.bump_governance_mint,
+            )?;
+            msg!("Minted {} governance tokens to {}", amount, recipient);
+        },
+        ProposalType::UpdateGovernanceConfig { voting_period, execution_delay, execution_window, proposal_threshold } => {
+            // Governance can't vote away its own safeguards: no instant votes, no instant execution
+            require!(
+                (MIN_VOTING_PERIOD..=MAX_VOTING_PERIOD).contains(&voting_period),
+                CustomError::InvalidParameter
+            );
+            require!(
+                (MIN_EXECUTION_DELAY..=MAX_EXECUTION_DELAY).contains(&execution_delay),
+                CustomError::InvalidParameter
+            );
+            require!(
+                (MIN_EXECUTION_WINDOW..=MAX_EXECUTION_WINDOW).contains(&execution_window),
+                CustomError::InvalidParameter
+            );
+            require!(proposal_threshold > 0, CustomError::InvalidParameter);
+            let governance_config = &mut ctx.accounts.governance_config;
+            governance_config.voting_period = voting_period;
+            governance_config.execution_delay = execution_delay;
+            governance_config.execution_window = execution_window;
+            governance_config.proposal_threshold = proposal_threshold;
+            msg!(
+                "Updated governance config: voting period={}s, execution delay={}s, execution window={}s, proposal threshold={}",
+                voting_period,
+                execution_delay,
+                execution_window,
+                proposal_threshold
+            );
+        },
+        ProposalType::SetGuardian(guardian) => {
+            // The default key leaves governance without a veto
+            ctx.accounts.governance_config.guardian = guardian;
+            msg!("Guardian set to {}", guardian);
+        },
+        ProposalType::UpdateProposalThresholds { tier, quorum_bps, approval_bps } => {
+            // Every tier needs some quorum and at least a simple majority
+            require!(
+                quorum_bps > 0 && quorum_bps <= BPS_DIVISOR as u64,
+                CustomError::InvalidParameter
+            );
+            require!(
+                approval_bps >= BPS_DIVISOR as u64 / 2 && approval_bps < BPS_DIVISOR as u64,
+                CustomError::InvalidParameter
+            );
+            let governance_config = &mut ctx.accounts.governance_config;
+            let thresholds = match tier {
+                ProposalTier::Standard => &mut governance_config.standard,
+                ProposalTier::Risk => &mut governance_config.risk,
+                ProposalTier::Critical => &mut governance_config.critical,
+            };
+            thresholds.quorum_bps = quorum_bps;
+            thresholds.approval_bps = approval_bps;
+            msg!("Updated {:?} proposals to {} bps quorum, {} bps approval", tier, quorum_bps, approval_bps);
+        },
+        ProposalType::ExecuteInstructions(instructions) => {
+            // I persist the executed status first, so an instruction can't re-enter and run this proposal again
+            ctx.accounts.proposal.status = ProposalStatus::Executed;
+            ctx.accounts.proposal.exit(&crate::ID)?;
+            
+            let market = ctx.accounts.config.key();
+            let authority_bump = ctx.accounts.governance_config.authority_bump;
+            for instruction in &instructions {
+                invoke_governance_instruction(instruction, ctx.remaining_accounts, &market, authority_bump)?;
+            }
+            
+            // The instructions may have called back into this program, so I pick up whatever they changed
+            // rather than writing my stale copies over it
+            ctx.accounts.config.reload()?;
+            ctx.accounts.oracle_config.reload()?;
+            ctx.accounts.oracle_state.reload()?;
+            ctx.accounts.governance_config.reload()?;
+            ctx.accounts.proposal.reload()?;
+            if let Some(collateral_config) = ctx.accounts.collateral_config.as_mut() {
+                collateral_config.reload()?;
+            }
+            msg!("Executed {} governance instructions", instructions.len());
+        },
     }
     
     // I mark the proposal as executed so it can't be executed again
@@ -97,3 +385,68 @@ pub fn process_execute_proposal(
     
     Ok(())
 }
+
+// Checks an optional account is the PDA the proposal writes, and creates it the first time with the executor paying
+// the rent; returns it with its bump and whether it already existed
+fn init_if_needed<'info>(
+    account: &Option<UncheckedAccount<'info>>,
+    seeds: &[&[u8]],
+    space: usize,
+    missing: CustomError,
+    executor: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<(AccountInfo<'info>, u8, bool)> {
+    let account = account.as_ref().ok_or(error!(missing))?.to_account_info();
+    let (address, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+    require_keys_eq!(account.key(), address, missing);
+    
+    let existed = account.owner == &crate::ID;
+    if !existed {
+        let bump_seed = [bump];
+        let signer_seeds = [seeds, &[&bump_seed[..]]].concat();
+        create_program_account(
+            &account,
+            &executor.to_account_info(),
+            &system_program.to_account_info(),
+            space,
+            &[&signer_seeds],
+        )?;
+    }
+    Ok((account, bump, existed))
+}
+
+// Runs one stored instruction with the governance authority PDA as signer
+fn invoke_governance_instruction<'info>(
+    instruction: &GovernanceInstruction,
+    remaining_accounts: &[AccountInfo<'info>],
+    market: &Pubkey,
+    authority_bump: u8,
+) -> Result<()> {
+    let find_account = |key: &Pubkey| {
+        remaining_accounts
+            .iter()
+            .find(|account| account.key == key)
+            .cloned()
+            .ok_or(error!(CustomError::MissingInstructionAccount))
+    };
+    
+    let mut account_infos = Vec::with_capacity(instruction.accounts.len() + 1);
+    for meta in &instruction.accounts {
+        account_infos.push(find_account(&meta.pubkey)?);
+    }
+    account_infos.push(find_account(&instruction.program_id)?);
+    
+    let ix = Instruction {
+        program_id: instruction.program_id,
+        accounts: instruction.accounts.iter().map(|meta| AccountMeta {
+            pubkey: meta.pubkey,
+            is_signer: meta.is_signer,
+            is_writable: meta.is_writable,
+        }).collect(),
+        data: instruction.data.clone(),
+    };
+    
+    let signer_seeds: &[&[&[u8]]] = &[&[SEED_GOVERNANCE_AUTHORITY, market.as_ref(), &[authority_bump]]];
+    invoke_signed(&ix, &account_infos, signer_seeds)?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_VOTER_WEIGHT, CustomError,
+    instructions::governance::deposit_governance_tokens::VoterWeight,
+};
+
+#[derive(Accounts)]
+pub struct ExtendLock<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), owner.key().as_ref()],
+        bump = voter_weight.bump,
+    )]
+    pub voter_weight: Account<'info, VoterWeight>,
+}
+
+pub fn process_extend_lock(ctx: Context<ExtendLock>, lock_duration: i64) -> Result<()> {
+    let clock = Clock::get()?;
+    
+    // A lock can only get longer, and never beyond the market's maximum
+    require!(
+        lock_duration > 0 && lock_duration <= ctx.accounts.config.max_lock_duration,
+        CustomError::InvalidParameter
+    );
+    let lock = ctx.accounts.voter_weight.current_lock();
+    let unlock_at = clock.unix_timestamp
+        .checked_add(lock_duration)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    require!(unlock_at > lock.unlock_at, CustomError::InvalidParameter);
+    
+    ctx.accounts.voter_weight.record(clock.slot, lock.balance, unlock_at);
+    
+    msg!(
+        "Voter {} locked {} governance tokens until {}",
+        ctx.accounts.owner.key(),
+        lock.balance,
+        unlock_at
+    );
+    
+    Ok(())
+}
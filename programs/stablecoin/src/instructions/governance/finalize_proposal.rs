@@ -0,0 +1,82 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, GovernanceConfig, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, BPS_DIVISOR, CustomError,
+    instructions::governance::create_proposal::{Proposal, ProposalStatus}
+};
+
+#[derive(Accounts)]
+#[instruction(proposal_id: u64)]
+pub struct FinalizeProposal<'info> {
+    // Anyone can finalize a proposal once voting is over
+    pub payer: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        seeds = [SEED_GOVERNANCE, config.key().as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        constraint = proposal.status == ProposalStatus::Active @ CustomError::ProposalAlreadyFinalized,
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+pub fn process_finalize_proposal(
+    ctx: Context<FinalizeProposal>,
+    _proposal_id: u64,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    
+    // I only count the votes once nobody can cast any more
+    let proposal = &mut ctx.accounts.proposal;
+    require!(
+        current_time > proposal.voting_ends_at,
+        CustomError::ProposalVotingInProgress
+    );
+    
+    // A proposal passes with a quorum and more than its approval share of the votes in favour. Quorum counts
+    // the locked balance that voted, since it's measured against the supply; approval weighs the decayed power
+    let total_votes = proposal.votes_for
+        .checked_add(proposal.votes_against)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    let quorum_reached = total_votes > 0 && proposal.quorum_balance >= proposal.quorum_votes;
+    let approved = proposal.votes_for as u128 * BPS_DIVISOR as u128
+        > total_votes as u128 * proposal.approval_bps as u128;
+    let passed = quorum_reached && approved;
+    
+    proposal.status = if passed { ProposalStatus::Passed } else { ProposalStatus::Rejected };
+    
+    // The execution delay runs from here, and the execution window after it. I fix both now, so governance
+    // changing its rules later can't shorten or stretch the timelock of a proposal that has already passed
+    proposal.finalized_at = current_time;
+    if passed {
+        let governance_config = &ctx.accounts.governance_config;
+        proposal.executable_at = current_time
+            .checked_add(governance_config.execution_delay)
+            .ok_or(error!(CustomError::MathOverflow))?;
+        proposal.expires_at = proposal.executable_at
+            .checked_add(governance_config.execution_window)
+            .ok_or(error!(CustomError::MathOverflow))?;
+    }
+    
+    msg!(
+        "Proposal {} finalized as {:?}: FOR={}, AGAINST={}, quorum reached={}",
+        proposal.id,
+        proposal.status,
+        proposal.votes_for,
+        proposal.votes_against,
+        quorum_reached
+    );
+    
+    Ok(())
+}
@@ -1,7 +1,25 @@
+pub mod deposit_governance_tokens;
+pub mod withdraw_governance_tokens;
+pub mod extend_lock;
+pub mod delegate;
+pub mod undelegate;
 pub mod create_proposal;
 pub mod vote_on_proposal;
+pub mod withdraw_vote;
+pub mod finalize_proposal;
 pub mod execute_proposal;
+pub mod cancel_proposal;
+pub mod close_proposal;
 
+pub use deposit_governance_tokens::*;
+pub use withdraw_governance_tokens::*;
+pub use extend_lock::*;
+pub use delegate::*;
+pub use undelegate::*;
 pub use create_proposal::*;
 pub use vote_on_proposal::*;
+pub use withdraw_vote::*;
+pub use finalize_proposal::*;
 pub use execute_proposal::*;
+pub use cancel_proposal::*;
+pub use close_proposal::*;
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_VOTER_WEIGHT, CustomError,
+    instructions::governance::deposit_governance_tokens::VoterWeight,
+};
+
+#[derive(Accounts)]
+pub struct Undelegate<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), owner.key().as_ref()],
+        bump = voter_weight.bump,
+    )]
+    pub voter_weight: Account<'info, VoterWeight>,
+}
+
+// Takes the voter's power back from proposals snapshotted from now on; earlier snapshots keep the old delegate
+pub fn process_undelegate(ctx: Context<Undelegate>) -> Result<()> {
+    let lock = ctx.accounts.voter_weight.current_lock();
+    require!(lock.delegate != Pubkey::default(), CustomError::InvalidDelegate);
+    
+    ctx.accounts.voter_weight.record_delegate(Clock::get()?.slot, Pubkey::default());
+    
+    msg!("Voter {} took back their delegation from {}", ctx.accounts.owner.key(), lock.delegate);
+    
+    Ok(())
+}
@@ -1,38 +1,112 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{TokenAccount, Token2022};
 use crate::{
-    SEED_GOVERNANCE, CustomError,
-    instructions::governance::create_proposal::{Proposal, ProposalStatus}
+    Config, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, SEED_VOTE_RECORD, SEED_VOTER_WEIGHT, CustomError, create_program_account,
+    instructions::governance::create_proposal::{Proposal, ProposalStatus},
+    instructions::governance::deposit_governance_tokens::VoterWeight,
 };
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
 pub enum VoteType {
     For,
     Against,
 }
 
+impl anchor_lang::Space for VoteType {
+    const INIT_SPACE: usize = 1;
+}
+
+// One voter's standing vote on one proposal, so the same power is never counted twice
+#[account]
+#[derive(InitSpace)]
+pub struct VoteRecord {
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    // The voter themselves, or the delegate who voted with their power
+    pub cast_by: Pubkey,
+    // Proposal ids can be reused once a proposal is closed, so I remember which one this vote was for
+    pub proposal_nonce: u64,
+    pub vote_type: VoteType,
+    pub voting_power: u64,
+    // The locked balance behind the vote, which counts towards quorum
+    pub balance: u64,
+    pub bump: u8,
+}
+
+impl VoteRecord {
+    // I take this record's power back out of the proposal's tally
+    pub fn retract(&self, proposal: &mut Proposal) -> Result<()> {
+        let tally = match self.vote_type {
+            VoteType::For => &mut proposal.votes_for,
+            VoteType::Against => &mut proposal.votes_against,
+        };
+        *tally = tally
+            .checked_sub(self.voting_power)
+            .ok_or(error!(CustomError::MathOverflow))?;
+        proposal.quorum_balance = proposal.quorum_balance
+            .checked_sub(self.balance)
+            .ok_or(error!(CustomError::MathOverflow))?;
+        Ok(())
+    }
+
+    // I add this record's power to the proposal's tally
+    pub fn count(&self, proposal: &mut Proposal) -> Result<()> {
+        let tally = match self.vote_type {
+            VoteType::For => &mut proposal.votes_for,
+            VoteType::Against => &mut proposal.votes_against,
+        };
+        *tally = tally
+            .checked_add(self.voting_power)
+            .ok_or(error!(CustomError::MathOverflow))?;
+        proposal.quorum_balance = proposal.quorum_balance
+            .checked_add(self.balance)
+            .ok_or(error!(CustomError::MathOverflow))?;
+        Ok(())
+    }
+}
+
 #[derive(Accounts)]
 #[instruction(proposal_id: u64, vote_type: VoteType)]
 pub struct VoteOnProposal<'info> {
     #[account(mut)]
     pub voter: Signer<'info>,
     
-    #[account(mut)]
-    pub voter_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    // Voters vote with the governance tokens they've deposited in this market; a delegate who never locked any
+    // leaves it out and votes only with the power delegated to them
+    #[account(
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), voter.key().as_ref()],
+        bump = voter_weight.bump,
+    )]
+    pub voter_weight: Option<Account<'info, VoterWeight>>,
     
     #[account(
         mut,
-        seeds = [SEED_GOVERNANCE, proposal_id.to_le_bytes().as_ref()],
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
         bump = proposal.bump,
         constraint = proposal.status == ProposalStatus::Active @ CustomError::ProposalVotingEnded,
     )]
     pub proposal: Account<'info, Proposal>,
     
-    pub token_program: Program<'info, Token2022>,
+    #[account(
+        init_if_needed,
+        payer = voter,
+        space = 8 + VoteRecord::INIT_SPACE,
+        seeds = [SEED_VOTE_RECORD, proposal.key().as_ref(), voter.key().as_ref()],
+        bump,
+    )]
+    pub vote_record: Account<'info, VoteRecord>,
+    
+    pub system_program: Program<'info, System>,
 }
 
-pub fn process_vote_on_proposal(
-    ctx: Context<VoteOnProposal>,
+// Delegates also pass a (voter weight, vote record) pair per delegator as remaining accounts
+pub fn process_vote_on_proposal<'info>(
+    ctx: Context<'_, '_, 'info, 'info, VoteOnProposal<'info>>,
     _proposal_id: u64,
     vote_type: VoteType,
 ) -> Result<()> {
@@ -46,46 +120,167 @@ pub fn process_vote_on_proposal(
         CustomError::ProposalVotingEnded
     );
     
-    // I use the voter's token balance as their voting power - more tokens means more influence
-    let voting_power = ctx.accounts.voter_token_account.amount;
+    // A voter changing their mind gets their earlier vote taken back first, and so does a delegate who voted for them
+    let proposal = &mut ctx.accounts.proposal;
+    let vote_record = &mut ctx.accounts.vote_record;
+    if vote_record.proposal_nonce == proposal.nonce {
+        vote_record.retract(proposal)?;
+    }
+    
+    // I use the voter's lock at the proposal's snapshot, so moving tokens around afterwards gains nothing,
+    // decayed to the time the proposal was created
+    let (voting_power, balance) = match &ctx.accounts.voter_weight {
+        Some(voter_weight) => {
+            let lock = voter_weight.lock_at(proposal.snapshot_slot);
+            (
+                lock.voting_power(proposal.created_at, ctx.accounts.config.max_lock_duration)?,
+                lock.quorum_balance(proposal.created_at),
+            )
+        },
+        None => {
+            require!(!ctx.remaining_accounts.is_empty(), CustomError::NoVotingPower);
+            (0, 0)
+        },
+    };
     
     // I handle both types of votes differently
     match vote_type {
         VoteType::For => {
             // I add their voting power to the "for" votes, being careful to avoid overflow
-            ctx.accounts.proposal.votes_for = ctx.accounts.proposal.votes_for.checked_add(voting_power)
+            proposal.votes_for = proposal.votes_for.checked_add(voting_power)
                 .ok_or(error!(CustomError::FeeCalculationError))?;
             
             // I log who voted and how much voting power they used
             msg!(
                 "Voter {} voted FOR proposal {} with {} voting power",
                 ctx.accounts.voter.key(),
-                ctx.accounts.proposal.id,
+                proposal.id,
                 voting_power
             );
         },
         VoteType::Against => {
             // I add their voting power to the "against" votes, being careful to avoid overflow
-            ctx.accounts.proposal.votes_against = ctx.accounts.proposal.votes_against.checked_add(voting_power)
+            proposal.votes_against = proposal.votes_against.checked_add(voting_power)
                 .ok_or(error!(CustomError::FeeCalculationError))?;
             
             // I log who voted against and how much voting power they used
             msg!(
                 "Voter {} voted AGAINST proposal {} with {} voting power",
                 ctx.accounts.voter.key(),
-                ctx.accounts.proposal.id,
+                proposal.id,
                 voting_power
             );
         },
     }
+    proposal.quorum_balance = proposal.quorum_balance
+        .checked_add(balance)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    
+    // I remember exactly what I counted, so it can be taken back later
+    vote_record.set_inner(VoteRecord {
+        proposal: proposal.key(),
+        voter: ctx.accounts.voter.key(),
+        cast_by: ctx.accounts.voter.key(),
+        proposal_nonce: proposal.nonce,
+        vote_type,
+        voting_power,
+        balance,
+        bump: ctx.bumps.vote_record,
+    });
+    
+    let delegated_power = cast_delegated_votes(ctx.accounts, ctx.remaining_accounts, vote_type)?;
+    if delegated_power > 0 {
+        msg!(
+            "Delegate {} voted {:?} with {} delegated voting power",
+            ctx.accounts.voter.key(),
+            vote_type,
+            delegated_power
+        );
+    }
     
     // I log the current vote totals for transparency
+    let proposal = &ctx.accounts.proposal;
     msg!(
         "Current vote totals for proposal {}: FOR={}, AGAINST={}",
-        ctx.accounts.proposal.id,
-        ctx.accounts.proposal.votes_for,
-        ctx.accounts.proposal.votes_against
+        proposal.id,
+        proposal.votes_for,
+        proposal.votes_against
     );
     
     Ok(())
 }
+
+// Votes with each delegator's power at the snapshot, writing it to the delegator's own vote record. A delegator who
+// voted directly keeps their vote, and voting directly later replaces the delegate's, so nothing is counted twice
+fn cast_delegated_votes<'info>(
+    accounts: &mut VoteOnProposal<'info>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    vote_type: VoteType,
+) -> Result<u64> {
+    require!(remaining_accounts.len().is_multiple_of(2), CustomError::InvalidParameter);
+    
+    let delegate = accounts.voter.key();
+    let market = accounts.config.key();
+    let max_lock_duration = accounts.config.max_lock_duration;
+    let proposal_key = accounts.proposal.key();
+    let mut delegated_power: u64 = 0;
+    
+    for pair in remaining_accounts.chunks(2) {
+        let (weight_info, record_info) = (&pair[0], &pair[1]);
+        
+        // Only power delegated to this voter as of the snapshot counts
+        let delegator_weight = Account::<VoterWeight>::try_from(weight_info)?;
+        let lock = delegator_weight.lock_at(accounts.proposal.snapshot_slot);
+        require!(
+            delegator_weight.market == market && lock.delegate == delegate,
+            CustomError::NotDelegated
+        );
+        let delegator = delegator_weight.owner;
+        
+        let (record_key, bump) = Pubkey::find_program_address(
+            &[SEED_VOTE_RECORD, proposal_key.as_ref(), delegator.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(record_info.key(), record_key, CustomError::InvalidParameter);
+        
+        if record_info.owner == &crate::ID {
+            let existing = Account::<VoteRecord>::try_from(record_info)?;
+            if existing.proposal_nonce == accounts.proposal.nonce {
+                if existing.cast_by != delegate {
+                    continue;
+                }
+                existing.retract(&mut accounts.proposal)?;
+            }
+        } else {
+            let signer_seeds: &[&[&[u8]]] = &[&[SEED_VOTE_RECORD, proposal_key.as_ref(), delegator.as_ref(), &[bump]]];
+            // The delegate pays for delegators' vote records
+            create_program_account(
+                record_info,
+                &accounts.voter.to_account_info(),
+                &accounts.system_program.to_account_info(),
+                8 + VoteRecord::INIT_SPACE,
+                signer_seeds,
+            )?;
+        }
+        
+        let voting_power = lock.voting_power(accounts.proposal.created_at, max_lock_duration)?;
+        let record = VoteRecord {
+            proposal: proposal_key,
+            voter: delegator,
+            cast_by: delegate,
+            proposal_nonce: accounts.proposal.nonce,
+            vote_type,
+            voting_power,
+            balance: lock.quorum_balance(accounts.proposal.created_at),
+            bump,
+        };
+        record.count(&mut accounts.proposal)?;
+        record.try_serialize(&mut &mut record_info.try_borrow_mut_data()?[..])?;
+        
+        delegated_power = delegated_power
+            .checked_add(voting_power)
+            .ok_or(error!(CustomError::MathOverflow))?;
+    }
+    
+    Ok(delegated_power)
+}
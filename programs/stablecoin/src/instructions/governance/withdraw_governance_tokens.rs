@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_VOTER_WEIGHT, CustomError,
+    instructions::governance::deposit_governance_tokens::VoterWeight,
+};
+
+#[derive(Accounts)]
+pub struct WithdrawGovernanceTokens<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = governance_mint,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_VOTER_WEIGHT, config.key().as_ref(), owner.key().as_ref()],
+        bump = voter_weight.bump,
+    )]
+    pub voter_weight: Account<'info, VoterWeight>,
+    
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        token::mint = governance_mint,
+        token::token_program = token_program,
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        associated_token::mint = governance_mint,
+        associated_token::authority = voter_weight,
+        associated_token::token_program = token_program,
+    )]
+    pub voter_weight_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn process_withdraw_governance_tokens(ctx: Context<WithdrawGovernanceTokens>, amount: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    
+    // Tokens stay locked until the lock ends, unless the market has been shut down
+    let voter_weight = &mut ctx.accounts.voter_weight;
+    let lock = voter_weight.current_lock();
+    require!(
+        clock.unix_timestamp >= lock.unlock_at || ctx.accounts.config.emergency_shutdown,
+        CustomError::GovernanceTokensLocked
+    );
+    
+    // Withdrawing only lowers future snapshots; votes on open proposals already read an earlier one
+    let balance = lock.balance
+        .checked_sub(amount)
+        .ok_or(error!(CustomError::InsufficientGovernanceBalance))?;
+    voter_weight.record(clock.slot, balance, lock.unlock_at);
+    
+    let market = ctx.accounts.config.key();
+    let owner = ctx.accounts.owner.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        SEED_VOTER_WEIGHT,
+        market.as_ref(),
+        owner.as_ref(),
+        &[ctx.accounts.voter_weight.bump],
+    ]];
+    transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.voter_weight_vault.to_account_info(),
+                mint: ctx.accounts.governance_mint.to_account_info(),
+                to: ctx.accounts.owner_token_account.to_account_info(),
+                authority: ctx.accounts.voter_weight.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        ctx.accounts.governance_mint.decimals,
+    )?;
+    
+    msg!(
+        "Voter {} withdrew {} governance tokens, balance {}",
+        owner,
+        amount,
+        balance
+    );
+    
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+use crate::{
+    Config, SEED_CONFIG_ACCOUNT, SEED_GOVERNANCE, SEED_VOTE_RECORD, CustomError,
+    instructions::governance::create_proposal::{Proposal, ProposalStatus},
+    instructions::governance::vote_on_proposal::VoteRecord,
+};
+
+#[derive(Accounts)]
+#[instruction(proposal_id: u64)]
+pub struct WithdrawVote<'info> {
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config.market_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [SEED_GOVERNANCE, config.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        constraint = proposal.status == ProposalStatus::Active @ CustomError::ProposalVotingEnded,
+    )]
+    pub proposal: Account<'info, Proposal>,
+    
+    // I hand the rent back once the vote is gone
+    #[account(
+        mut,
+        close = voter,
+        seeds = [SEED_VOTE_RECORD, proposal.key().as_ref(), voter.key().as_ref()],
+        bump = vote_record.bump,
+        constraint = vote_record.proposal_nonce == proposal.nonce @ CustomError::NoVoteCast,
+        // A vote cast by a delegate is replaced by voting directly, not withdrawn
+        constraint = vote_record.cast_by == voter.key() @ CustomError::NoVoteCast,
+    )]
+    pub vote_record: Account<'info, VoteRecord>,
+}
+
+pub fn process_withdraw_vote(
+    ctx: Context<WithdrawVote>,
+    _proposal_id: u64,
+) -> Result<()> {
+    // Votes can only be taken back while voting is open
+    require!(
+        Clock::get()?.unix_timestamp <= ctx.accounts.proposal.voting_ends_at,
+        CustomError::ProposalVotingEnded
+    );
+    
+    ctx.accounts.vote_record.retract(&mut ctx.accounts.proposal)?;
+    
+    msg!(
+        "Voter {} withdrew {} voting power from proposal {}: FOR={}, AGAINST={}",
+        ctx.accounts.voter.key(),
+        ctx.accounts.vote_record.voting_power,
+        ctx.accounts.proposal.id,
+        ctx.accounts.proposal.votes_for,
+        ctx.accounts.proposal.votes_against
+    );
+    
+    Ok(())
+}
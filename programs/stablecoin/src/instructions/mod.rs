@@ -3,9 +3,15 @@ pub use deposit::*;
 pub use utils::*;
 pub use withdraw::*;
 pub use governance::*;
+pub use oracle::*;
+pub use emode::*;
+pub use facilitator::*;
 
 pub mod admin;
 pub mod deposit;
 pub mod utils;
 pub mod withdraw;
-pub mod governance;
\ No newline at end of file
+pub mod governance;
+pub mod oracle;
+pub mod emode;
+pub mod facilitator;
\ No newline at end of file
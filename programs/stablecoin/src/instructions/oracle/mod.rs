@@ -0,0 +1,3 @@
+pub use refresh_oracle_price::*;
+
+pub mod refresh_oracle_price;
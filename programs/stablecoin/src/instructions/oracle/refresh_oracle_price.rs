@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+use crate::{
+    state::{Config, OracleConfig, OracleState},
+    SEED_CONFIG_ACCOUNT, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE, load_validated_price,
+};
+
+#[derive(Accounts)]
+pub struct RefreshOraclePrice<'info> {
+    // Anyone can crank the oracle state, so a trip is recorded even when no one is trading
+    pub payer: Signer<'info>,
+
+    // Each market has its own oracle config and circuit breaker
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+    )]
+    pub config_account: Account<'info, Config>,
+
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Account<'info, OracleState>,
+
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: UncheckedAccount<'info>,
+}
+
+pub fn process_refresh_oracle_price(ctx: Context<RefreshOraclePrice>) -> Result<()> {
+    let price = load_validated_price(
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &ctx.accounts.price_update,
+    )?;
+
+    msg!(
+        "Oracle price refreshed: {} at {}, circuit breaker tripped: {}",
+        price.spot_price,
+        price.publish_time,
+        ctx.accounts.oracle_state.circuit_breaker_tripped
+    );
+
+    Ok(())
+}
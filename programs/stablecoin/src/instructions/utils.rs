@@ -1,30 +1,34 @@
 use anchor_lang::{prelude::*, solana_program::native_token::LAMPORTS_PER_SOL};
-use pyth_solana_receiver_sdk::price_update::{PriceUpdateV2, get_feed_id_from_hex};
+use anchor_lang::system_program::{allocate, assign, transfer, Allocate, Assign, Transfer};
+use solana_stake_interface::{self as stake, state::StakeStateV2};
 
 use crate::{
-    Collateral, Config, CustomError, 
-    FEED_ID, BACKUP_FEED_ID, MAXIMUM_AGE, PRICE_FEED_DECIMAL_ADJUSTMENT,
-    ORACLE_CONFIDENCE_THRESHOLD, MAX_ORACLE_PRICE_DEVIATION, 
-    CRITICAL_HEALTH_FACTOR, MAX_COLLATERAL_RATIO, MIN_COLLATERAL_RATIO,
-    VOLATILITY_ADJUSTMENT, MAX_MINT_AMOUNT, BPS_DIVISOR
+    Collateral, CollateralConfig, CollateralKind, CollateralPosition, Config, CustomError, DebtTier, Decimal, EModeCategory, OracleConfig,
+    OraclePrice, OracleState, PriceSource, PythPriceSource, StakePoolExchangeRate, load_price_source,
+    mul_div_ceil, mul_div_floor,
+    MAXIMUM_AGE, PRICE_DECIMALS, MIN_PRICE_EXPONENT, MAX_PRICE_EXPONENT,
+    MAX_ORACLE_PRICE_DEVIATION, 
+    FALLBACK_BASE_MARGIN_BPS, FALLBACK_MARGIN_BPS_PER_HOUR, FALLBACK_MAX_MARGIN_BPS, FALLBACK_MAX_AGE,
+    CRITICAL_HEALTH_FACTOR_BPS, MIN_HEALTH_FACTOR_BPS, MAX_MIN_HEALTH_FACTOR_BPS, MAX_COLLATERAL_RATIO, MIN_COLLATERAL_RATIO,
+    MAX_DEBT_TIER_EXTRA_COLLATERAL_RATIO,
+    VOLATILITY_ADJUSTMENT, MAX_MINT_AMOUNT, MAX_COLLATERAL_POSITIONS, NO_EMODE_CATEGORY, BPS_DIVISOR
 };
 
 pub fn check_health_factor(
-    collateral_amount: u64,
-    collateral: &Account<Collateral>,
-    config: &Account<Config>,
-    price_feed: &Account<PriceUpdateV2>,
+    valuation: &CollateralValuation,
+    collateral: &Collateral,
+    config: &Config,
 ) -> Result<()> {
-    let health_factor = calculate_health_factor(collateral_amount, collateral, config, price_feed)?;
+    let health_factor = calculate_health_factor(valuation, collateral, config)?;
     
     // I need to make sure the position is safe by checking against my minimum threshold
     require!(
-        health_factor >= config.min_health_factor,
+        health_factor >= Decimal::from_bps(config.min_health_factor_bps),
         CustomError::BelowMinHealthFactor
     );
     
     // I like to warn users when they're getting close to the danger zone
-    if health_factor < CRITICAL_HEALTH_FACTOR {
+    if health_factor < Decimal::from_bps(CRITICAL_HEALTH_FACTOR_BPS) {
         msg!("Warning: Health factor approaching critical level: {}", health_factor);
     }
     
@@ -32,58 +36,419 @@ pub fn check_health_factor(
 }
 
 pub fn calculate_health_factor (
-    collateral_amount: u64,
-    collateral: &Account<Collateral>,
-    config: &Account<Config>,
-    price_feed: &Account<PriceUpdateV2>,
-) -> Result<u64> {
-    // I first get the USD value using my fancy multi-oracle setup for extra safety
-    let collateral_value_in_usd = get_validated_usd_value(collateral_amount, price_feed)?;
-    
-    // I apply a volatility adjustment because crypto prices can swing wildly
-    let volatility_adjusted_value = apply_volatility_adjustment(collateral_value_in_usd)?;
-    
-    // I use the liquidation threshold to determine how much of the collateral I'll count
-    let collateral_adjusted_for_liquidation_threshold = 
-        (volatility_adjusted_value * config.liquidation_threshold) / 100;
-
+    valuation: &CollateralValuation,
+    collateral: &Collateral,
+    config: &Config,
+) -> Result<Decimal> {
     // If they haven't minted anything yet, their position is perfectly safe
     if collateral.amount_minted == 0 {
         msg!("Health factor Max");
-        return Ok(u64::MAX); 
+        return Ok(Decimal::MAX); 
     }
     
     // I check if they're over or under collateralized compared to my recommended ranges
-    let collateralization_ratio = (collateral_value_in_usd * 100) / collateral.amount_minted;
+    let collateralization_ratio = calculate_collateralization_ratio(valuation.total_value, collateral.amount_minted)?;
     
-    if collateralization_ratio > MAX_COLLATERAL_RATIO {
-        msg!("Warning: Excessive collateralization: {}%", collateralization_ratio);
-    } else if collateralization_ratio < MIN_COLLATERAL_RATIO {
-        msg!("Warning: Low collateralization: {}%", collateralization_ratio);
+    if collateralization_ratio > Decimal::from_percent(MAX_COLLATERAL_RATIO) {
+        msg!("Warning: Excessive collateralization: {}", collateralization_ratio);
+    } else if collateralization_ratio < Decimal::from_percent(MIN_COLLATERAL_RATIO) {
+        msg!("Warning: Low collateralization: {}", collateralization_ratio);
     }
     
-    // Finally, I calculate the health factor - this is the core of my risk management
-    let health_factor = (collateral_adjusted_for_liquidation_threshold) / collateral.amount_minted;
-    Ok(health_factor)
+    // Large vaults get every threshold scaled down by their debt tier's haircut
+    let mut liquidation_value = valuation.liquidation_value;
+    if let Some(tier) = get_debt_tier(config, collateral.amount_minted) {
+        let retained_bps = BPS_DIVISOR as u64 - tier.threshold_haircut_bps;
+        liquidation_value = liquidation_value.try_mul(Decimal::from_bps(retained_bps))?;
+    }
+    
+    // Finally, I calculate the health factor - rounding down so a position never looks safer than it is
+    liquidation_value.try_div(Decimal::from_u64(collateral.amount_minted))
+}
+
+// The highest debt tier a vault's debt has reached, if any
+pub fn get_debt_tier(config: &Config, debt: u64) -> Option<&DebtTier> {
+    config.debt_tiers
+        .iter()
+        .filter(|tier| tier.min_debt > 0 && debt >= tier.min_debt)
+        .max_by_key(|tier| tier.min_debt)
+}
+
+// Bigger vaults must never get looser terms: ordered by min_debt, the active tiers' haircuts and extra
+// ratios can only go up. Unused tiers (min_debt 0) are ignored.
+pub fn validate_debt_tiers(debt_tiers: &[DebtTier]) -> Result<()> {
+    let mut active: Vec<&DebtTier> = debt_tiers.iter().filter(|tier| tier.min_debt > 0).collect();
+    active.sort_by_key(|tier| tier.min_debt);
+
+    for tier in &active {
+        require!(tier.threshold_haircut_bps < BPS_DIVISOR as u64, CustomError::InvalidParameter);
+        require!(
+            tier.extra_collateral_ratio <= MAX_DEBT_TIER_EXTRA_COLLATERAL_RATIO,
+            CustomError::InvalidParameter
+        );
+    }
+    for pair in active.windows(2) {
+        let (smaller, larger) = (pair[0], pair[1]);
+        require!(
+            larger.min_debt > smaller.min_debt
+                && larger.threshold_haircut_bps >= smaller.threshold_haircut_bps
+                && larger.extra_collateral_ratio >= smaller.extra_collateral_ratio,
+            CustomError::InvalidParameter
+        );
+    }
+    Ok(())
+}
+
+// The collateral ratio a vault needs to mint: its e-mode category's or the protocol minimum,
+// plus whatever its debt tier adds on top
+pub fn get_min_collateral_ratio(config: &Config, emode: Option<&EModeCategory>, debt: u64) -> Result<u64> {
+    let base_ratio = emode.map_or(MIN_COLLATERAL_RATIO, |category| category.min_collateral_ratio);
+    let extra_ratio = get_debt_tier(config, debt).map_or(0, |tier| tier.extra_collateral_ratio);
+    base_ratio
+        .checked_add(extra_ratio)
+        .ok_or(error!(CustomError::MathOverflow))
+}
+
+// What a vault's collateral is worth: in full, and the part of it that counts towards health
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CollateralValuation {
+    // The USD value of everything in the vault
+    pub total_value: u64,
+
+    // The sum of each asset's volatility-adjusted value times its own liquidation threshold
+    pub liquidation_value: Decimal,
+}
+
+impl CollateralValuation {
+    fn add(&mut self, value_in_usd: u64, liquidation_threshold: u64) -> Result<()> {
+        self.total_value = self.total_value
+            .checked_add(value_in_usd)
+            .ok_or(error!(CustomError::MathOverflow))?;
+
+        // I apply a volatility adjustment because crypto prices can swing wildly,
+        // then only count the share of the asset its liquidation threshold allows
+        let counted_value = apply_volatility_adjustment(Decimal::from_u64(value_in_usd))?
+            .try_mul(Decimal::from_percent(liquidation_threshold))?;
+        self.liquidation_value = self.liquidation_value.try_add(counted_value)?;
+        Ok(())
+    }
+}
+
+// I value the vault's SOL and native stake at the SOL price with the protocol-wide threshold,
+// and every token it holds at that token's own price and threshold - unless the vault is in
+// an e-mode category the asset belongs to, in which case the category's threshold applies
+pub fn value_collateral(
+    collateral: &Collateral,
+    config: &Config,
+    emode: Option<&EModeCategory>,
+    sol_lamports: u64,
+    price: &ValidatedPrice,
+    asset_prices: &[AssetPrice],
+) -> Result<CollateralValuation> {
+    let native_lamports = sol_lamports
+        .checked_add(collateral.stake_lamports)
+        .ok_or(error!(CustomError::MathOverflow))?;
+
+    let sol_threshold = sol_emode(emode)
+        .map_or(config.liquidation_threshold, |category| category.liquidation_threshold);
+
+    let mut valuation = CollateralValuation::default();
+    valuation.add(get_validated_usd_value(native_lamports, price)?, sol_threshold)?;
+    for position in &collateral.positions {
+        let asset_price = find_asset_price(asset_prices, &position.mint)?;
+        let threshold = asset_price
+            .emode(emode)
+            .map_or(asset_price.liquidation_threshold, |category| category.liquidation_threshold);
+        valuation.add(asset_price.usd_value(position.amount, price)?, threshold)?;
+    }
+    Ok(valuation)
+}
+
+pub fn calculate_collateralization_ratio(collateral_value_in_usd: u64, amount_minted: u64) -> Result<Decimal> {
+    // Collateral over debt, rounded down against the user
+    Decimal::from_ratio(collateral_value_in_usd, amount_minted)
 }
 
-fn apply_volatility_adjustment(value: u64) -> Result<u64> {
+fn apply_volatility_adjustment(value: Decimal) -> Result<Decimal> {
     // I reduce the collateral value a bit to account for market volatility
-    let adjusted_value = value * (100 - VOLATILITY_ADJUSTMENT) / 100;
-    Ok(adjusted_value)
+    value.try_mul(Decimal::from_percent(100 - VOLATILITY_ADJUSTMENT))
+}
+
+// The oracle price after validation, normalized to PRICE_DECIMALS and split into the
+// conservative bounds I use on each side of a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValidatedPrice {
+    // The mid-market price the oracle reported
+    pub spot_price: u64,
+
+    // What I value collateral at: min(spot - k·conf, ema - k·ema_conf)
+    pub collateral_price: u64,
+
+    // What I convert debt at: max(spot + k·conf, ema + k·ema_conf)
+    pub debt_price: u64,
+
+    // When the oracle published the price
+    pub publish_time: i64,
+
+    // Set when no oracle was fresh and this is the last-good price with widened bounds
+    pub is_fallback: bool,
+}
+
+impl ValidatedPrice {
+    // I re-quote a USD price in the market's currency, keeping each bound on its conservative side
+    pub fn in_currency(&self, target_price: &ValidatedPrice) -> Result<ValidatedPrice> {
+        let scale = 10u64.pow(PRICE_DECIMALS as u32);
+        let collateral_price = mul_div_floor(self.collateral_price, scale, target_price.debt_price)?;
+        require!(collateral_price > 0, CustomError::InvalidPrice);
+
+        Ok(ValidatedPrice {
+            spot_price: mul_div_floor(self.spot_price, scale, target_price.spot_price)?,
+            collateral_price,
+            debt_price: mul_div_ceil(self.debt_price, scale, target_price.collateral_price)?,
+            publish_time: self.publish_time,
+            is_fallback: self.is_fallback,
+        })
+    }
+}
+
+// Every instruction tells me how it changes a position, and that decides what price it needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RiskProfile {
+    // Top-ups, repayments and withdrawing from a repaid vault can only make things safer,
+    // so they don't need a price at all
+    Reducing,
+
+    // Minting and withdrawing collateral against debt need a fresh price and a quiet market
+    Increasing,
+
+    // Liquidations need a price, but I let them run on the fallback price too
+    Liquidation,
+}
+
+impl RiskProfile {
+    pub fn for_position_change(debt_added: u64, collateral_removed: u64, debt_after: u64) -> Self {
+        // Once the debt is gone there's nothing left to protect
+        if debt_after > 0 && (debt_added > 0 || collateral_removed > 0) {
+            Self::Increasing
+        } else {
+            Self::Reducing
+        }
+    }
+
+    pub fn increases_risk(self) -> bool {
+        self == Self::Increasing
+    }
+}
+
+pub fn load_price_for_operation(
+    risk_profile: RiskProfile,
+    oracle_config: &OracleConfig,
+    oracle_state: &mut OracleState,
+    collateral: &mut Collateral,
+    price_account: Option<&AccountInfo>,
+    target_price_account: Option<&AccountInfo>,
+) -> Result<Option<ValidatedPrice>> {
+    let price_account = match (risk_profile, price_account) {
+        (_, Some(price_account)) => price_account,
+        (RiskProfile::Reducing, None) => return Ok(None),
+        (_, None) => return err!(CustomError::MissingPriceAccount),
+    };
+
+    let price = load_validated_price(oracle_config, oracle_state, price_account)?;
+    record_position_price(collateral, &price)?;
+
+    // Adding risk waits for a live price and a tripped circuit breaker to clear
+    if risk_profile.increases_risk() {
+        check_circuit_breaker(oracle_state)?;
+        check_fresh_price(&price)?;
+    }
+
+    // Markets that aren't denominated in USD value everything in their own currency
+    match load_target_price(oracle_config, target_price_account)? {
+        Some(target_price) => Ok(Some(price.in_currency(&target_price)?)),
+        None => Ok(Some(price)),
+    }
+}
+
+// The USD price of the market's currency, or None for a USD market
+pub fn load_target_price(
+    oracle_config: &OracleConfig,
+    target_price_account: Option<&AccountInfo>,
+) -> Result<Option<ValidatedPrice>> {
+    if oracle_config.target_price_account == Pubkey::default() {
+        return Ok(None);
+    }
+    let target_price_account = target_price_account.ok_or(error!(CustomError::MissingPriceAccount))?;
+
+    // I only read the currency feed pinned when the market was created
+    require_keys_eq!(
+        target_price_account.key(),
+        oracle_config.target_price_account,
+        CustomError::InvalidPriceAccount
+    );
+    let price_source = PythPriceSource::from_account_info_for_feed(target_price_account, oracle_config.target_feed_id)?;
+    Ok(Some(get_validated_price(&price_source, oracle_config)?))
+}
+
+pub fn load_validated_price(
+    oracle_config: &OracleConfig,
+    oracle_state: &mut OracleState,
+    price_account: &AccountInfo,
+) -> Result<ValidatedPrice> {
+    // I read prices through whichever adapter the oracle config selects
+    let price_source = load_price_source(oracle_config, price_account)?;
+    let price = match get_validated_price(price_source.as_ref(), oracle_config) {
+        Ok(price) => price,
+        // If every oracle is stale I fall back to the last price I accepted
+        Err(err) if err == error!(CustomError::StaleOracleData) => {
+            return get_fallback_price(oracle_state, &Clock::get()?);
+        },
+        Err(err) => return Err(err),
+    };
+
+    // Every price I accept goes through the circuit breaker
+    record_oracle_price(oracle_state, oracle_config, &price)?;
+    Ok(price)
+}
+
+pub fn get_fallback_price(oracle_state: &mut OracleState, clock: &Clock) -> Result<ValidatedPrice> {
+    // I can only fall back on a price I've actually seen, and not an ancient one
+    let staleness = clock.unix_timestamp.saturating_sub(oracle_state.last_price_timestamp);
+    require!(
+        oracle_state.last_price > 0 && staleness <= FALLBACK_MAX_AGE,
+        CustomError::StaleOracleData
+    );
+
+    // The longer I go without a fresh price, the less I trust the old one
+    let staleness_margin_bps = mul_div_floor(staleness.max(0) as u64, FALLBACK_MARGIN_BPS_PER_HOUR, 3600)?;
+    let margin_bps = FALLBACK_BASE_MARGIN_BPS
+        .saturating_add(staleness_margin_bps)
+        .min(FALLBACK_MAX_MARGIN_BPS);
+    let margin = mul_div_ceil(oracle_state.last_price, margin_bps, BPS_DIVISOR as u64)?;
+
+    let collateral_price = oracle_state.last_price.saturating_sub(margin);
+    require!(collateral_price > 0, CustomError::InvalidPrice);
+    let debt_price = oracle_state.last_price
+        .checked_add(margin)
+        .ok_or(error!(CustomError::MathOverflow))?;
+
+    if !oracle_state.fallback_mode {
+        msg!("Warning: All oracles stale, entering fallback mode");
+        oracle_state.fallback_mode = true;
+    }
+    msg!(
+        "Warning: Using fallback price {} from {}s ago with a {} bps margin",
+        oracle_state.last_price,
+        staleness,
+        margin_bps
+    );
+
+    Ok(ValidatedPrice {
+        spot_price: oracle_state.last_price,
+        collateral_price,
+        debt_price,
+        publish_time: oracle_state.last_price_timestamp,
+        is_fallback: true,
+    })
+}
+
+pub fn record_position_price(collateral: &mut Collateral, price: &ValidatedPrice) -> Result<()> {
+    // I never let a position be valued at an older price than it was last time
+    require!(
+        price.publish_time >= collateral.last_price_publish_time,
+        CustomError::OutdatedPriceUpdate
+    );
+    collateral.last_price_publish_time = price.publish_time;
+    Ok(())
+}
+
+pub fn check_fresh_price(price: &ValidatedPrice) -> Result<()> {
+    // Operations that add risk need a live oracle price
+    require!(!price.is_fallback, CustomError::OracleFallbackRestricted);
+    Ok(())
+}
+
+pub fn record_oracle_price(
+    oracle_state: &mut OracleState,
+    oracle_config: &OracleConfig,
+    price: &ValidatedPrice,
+) -> Result<()> {
+    // A fresh price is all it takes to leave fallback mode
+    if oracle_state.fallback_mode {
+        msg!("Fresh oracle price accepted, leaving fallback mode");
+        oracle_state.fallback_mode = false;
+    }
+
+    // I ignore prices that aren't newer than the one I already have
+    if price.publish_time <= oracle_state.last_price_timestamp {
+        return Ok(());
+    }
+
+    // I start a new comparison window from the last price I accepted once the old one has run its course,
+    // so a move landing just after the boundary, or after a gap in updates, is still measured from before it
+    if oracle_state.reference_price == 0 {
+        oracle_state.reference_price = price.spot_price;
+        oracle_state.reference_timestamp = price.publish_time;
+    } else if price.publish_time.saturating_sub(oracle_state.reference_timestamp) > oracle_config.price_move_window {
+        oracle_state.reference_price = oracle_state.last_price;
+        oracle_state.reference_timestamp = oracle_state.last_price_timestamp;
+    }
+    let window_elapsed = price.publish_time.saturating_sub(oracle_state.reference_timestamp);
+
+    // I measure how far the price has moved since the window started, and since the last update
+    let price_move_bps = move_in_bps(oracle_state.reference_price, price.spot_price)?
+        .max(move_in_bps(oracle_state.last_price, price.spot_price)?);
+    if price_move_bps > oracle_config.max_price_move_bps && !oracle_state.circuit_breaker_tripped {
+        msg!(
+            "Warning: Price moved {} bps within {}s, tripping circuit breaker",
+            price_move_bps,
+            window_elapsed
+        );
+        oracle_state.circuit_breaker_tripped = true;
+        oracle_state.tripped_at = price.publish_time;
+    }
+
+    oracle_state.last_price = price.spot_price;
+    oracle_state.last_price_timestamp = price.publish_time;
+    Ok(())
 }
 
-pub fn get_validated_usd_value(amount_in_lamports: u64, price_feed: &Account<PriceUpdateV2>) -> Result<u64> {
+// How far the price has moved from an earlier one, in basis points; nothing to compare against counts as no move
+fn move_in_bps(from: u64, to: u64) -> Result<u64> {
+    if from == 0 {
+        return Ok(0);
+    }
+    mul_div_ceil(from.abs_diff(to), BPS_DIVISOR as u64, from)
+}
+
+pub fn clear_circuit_breaker(oracle_state: &mut OracleState) {
+    // I measure future moves from the last accepted price, not the pre-trip one
+    oracle_state.circuit_breaker_tripped = false;
+    oracle_state.tripped_at = 0;
+    oracle_state.reference_price = oracle_state.last_price;
+    oracle_state.reference_timestamp = oracle_state.last_price_timestamp;
+}
+
+pub fn check_circuit_breaker(oracle_state: &OracleState) -> Result<()> {
+    // Minting and withdrawals wait until the circuit breaker is reset
+    require!(!oracle_state.circuit_breaker_tripped, CustomError::CircuitBreakerTripped);
+    Ok(())
+}
+
+pub fn get_validated_price(price_feed: &dyn PriceSource, oracle_config: &OracleConfig) -> Result<ValidatedPrice> {
     // I always start with my primary oracle - Pyth is pretty reliable
-    let primary_price = get_price_from_feed(FEED_ID, price_feed)?;
+    let clock = Clock::get()?;
+    let primary_price = get_price_from_feed(price_feed.get_price(&clock, MAXIMUM_AGE), oracle_config)?;
     
     // I try to get a second opinion from my backup oracle
-    let backup_price_result = get_price_from_feed(BACKUP_FEED_ID, price_feed);
+    let backup_price_result = price_feed
+        .get_backup_price(&clock, MAXIMUM_AGE)
+        .map(|price| get_price_from_feed(price, oracle_config));
     
     match backup_price_result {
-        Ok(backup_price) => {
+        Some(Ok(backup_price)) => {
             // I check if my oracles are giving me consistent prices
-            let deviation = calculate_price_deviation(primary_price, backup_price);
+            let deviation = calculate_price_deviation(primary_price.spot_price, backup_price.spot_price)?;
             
             // If they disagree too much, something fishy might be going on
             if deviation > MAX_ORACLE_PRICE_DEVIATION as u64 {
@@ -91,82 +456,561 @@ pub fn get_validated_usd_value(amount_in_lamports: u64, price_feed: &Account<Pri
                 return Err(error!(CustomError::OraclePriceDeviation));
             }
             
-            // Two heads are better than one - I use the average price for better accuracy
-            let average_price = (primary_price + backup_price) / 2;
-            let price_in_usd = average_price as u128 * PRICE_FEED_DECIMAL_ADJUSTMENT;
-            let amount_in_usd = (amount_in_lamports as u128 * price_in_usd) / (LAMPORTS_PER_SOL as u128);
-            Ok(amount_in_usd as u64)
+            // Two heads are better than one - I take the more cautious bound from either oracle
+            Ok(ValidatedPrice {
+                spot_price: ((primary_price.spot_price as u128 + backup_price.spot_price as u128) / 2) as u64,
+                collateral_price: primary_price.collateral_price.min(backup_price.collateral_price),
+                debt_price: primary_price.debt_price.max(backup_price.debt_price),
+                publish_time: primary_price.publish_time,
+                is_fallback: false,
+            })
+        },
+        Some(Err(err)) => {
+            // If my backup oracle is down, I can still work with just the primary, but I say why
+            msg!("Warning: Backup oracle price unavailable ({}), using only primary oracle price feed", err);
+            Ok(primary_price)
         },
-        Err(_) => {
-            // If my backup oracle is down, I can still work with just the primary
-            msg!("Warning: Using only primary oracle price feed");
-            let price_in_usd = primary_price as u128 * PRICE_FEED_DECIMAL_ADJUSTMENT;
-            let amount_in_usd = (amount_in_lamports as u128 * price_in_usd) / (LAMPORTS_PER_SOL as u128);
-            Ok(amount_in_usd as u64)
+        None => Ok(primary_price),
+    }
+}
+
+pub fn get_validated_usd_value(amount_in_lamports: u64, price: &ValidatedPrice) -> Result<u64> {
+    // I value collateral at the low end of the oracle's range, rounding down
+    mul_div_floor(amount_in_lamports, price.collateral_price, LAMPORTS_PER_SOL)
+}
+
+// LSTs don't get their own thin USD feed - I price them as SOL/USD times the pool's exchange rate
+pub fn get_lst_usd_value(
+    lst_amount: u64,
+    exchange_rate: &StakePoolExchangeRate,
+    price: &ValidatedPrice,
+) -> Result<u64> {
+    get_validated_usd_value(exchange_rate.lamports_for(lst_amount)?, price)
+}
+
+// How I price one of the tokens a vault holds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetPricing {
+    // A liquid staking token, through its stake pool's exchange rate and the SOL price
+    StakePool(StakePoolExchangeRate),
+
+    // A token with its own USD price, quoted per whole token of `decimals` decimals
+    Oracle { price: ValidatedPrice, decimals: u8 },
+}
+
+// A collateral token's registry parameters, together with what it's worth right now
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AssetPrice {
+    pub mint: Pubkey,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+    pub emode_category: u8,
+    pub pricing: AssetPricing,
+}
+
+impl AssetPrice {
+    // A token is judged by its e-mode category's parameters when the vault is in that category
+    pub fn emode<'a>(&self, emode: Option<&'a EModeCategory>) -> Option<&'a EModeCategory> {
+        emode.filter(|category| category.id == self.emode_category)
+    }
+
+    // The USD value of `amount` base units, at the low end of the price and rounded down
+    pub fn usd_value(&self, amount: u64, sol_price: &ValidatedPrice) -> Result<u64> {
+        match &self.pricing {
+            AssetPricing::StakePool(exchange_rate) => get_lst_usd_value(amount, exchange_rate, sol_price),
+            AssetPricing::Oracle { price, decimals } => {
+                mul_div_floor(amount, price.collateral_price, 10u64.pow(*decimals as u32))
+            }
+        }
+    }
+
+    // How many base units cover `amount_in_usd` of debt, at the high end of the price and rounded down
+    pub fn amount_for_usd(&self, amount_in_usd: u64, sol_price: &ValidatedPrice) -> Result<u64> {
+        match &self.pricing {
+            AssetPricing::StakePool(exchange_rate) => {
+                exchange_rate.pool_tokens_for(get_lamports_from_usd(&amount_in_usd, sol_price)?)
+            }
+            AssetPricing::Oracle { price, decimals } => {
+                require!(amount_in_usd <= MAX_MINT_AMOUNT, CustomError::ExcessiveMintAmount);
+                mul_div_floor(amount_in_usd, 10u64.pow(*decimals as u32), price.debt_price)
+            }
+        }
+    }
+}
+
+// Every listed token needs a threshold that counts some of its value, and a sane liquidation bonus
+pub fn validate_collateral_params(liquidation_threshold: u64, liquidation_bonus: u64) -> Result<()> {
+    require!(
+        liquidation_threshold > 0 && liquidation_threshold <= 100 && liquidation_bonus <= 100,
+        CustomError::InvalidParameter
+    );
+    Ok(())
+}
+
+// A vault below a health factor of 1.0 is already past its liquidation threshold, so the minimum can't go lower
+pub fn validate_min_health_factor(min_health_factor_bps: u64) -> Result<()> {
+    require!(
+        (MIN_HEALTH_FACTOR_BPS..=MAX_MIN_HEALTH_FACTOR_BPS).contains(&min_health_factor_bps),
+        CustomError::InvalidParameter
+    );
+    Ok(())
+}
+
+pub fn find_asset_price<'a>(asset_prices: &'a [AssetPrice], mint: &Pubkey) -> Result<&'a AssetPrice> {
+    asset_prices
+        .iter()
+        .find(|asset_price| asset_price.mint == *mint)
+        .ok_or(error!(CustomError::MissingCollateralAccounts))
+}
+
+// Each token position is priced from two remaining accounts, in the vault's position order:
+// the token's CollateralConfig entry, then the stake pool or Pyth price account it pins
+pub fn load_asset_prices(
+    collateral: &Collateral,
+    oracle_config: &OracleConfig,
+    target_price_account: Option<&AccountInfo>,
+    remaining_accounts: &[AccountInfo],
+) -> Result<Vec<AssetPrice>> {
+    let position_configs = load_position_configs(collateral, remaining_accounts)?;
+
+    // Tokens with their own USD feed need the market's currency price too
+    let target_price = if position_configs.iter().any(|config| config.kind == CollateralKind::OracleToken) {
+        load_target_price(oracle_config, target_price_account)?
+    } else {
+        None
+    };
+
+    position_configs
+        .iter()
+        .zip(remaining_accounts.chunks_exact(2))
+        .map(|(collateral_config, accounts)| {
+            load_asset_price(collateral_config, &accounts[1], oracle_config, target_price.as_ref())
+        })
+        .collect()
+}
+
+// The registry entries of every token the vault holds, from the same remaining accounts layout
+pub fn load_position_configs(
+    collateral: &Collateral,
+    remaining_accounts: &[AccountInfo],
+) -> Result<Vec<CollateralConfig>> {
+    require!(
+        remaining_accounts.len() >= collateral.positions.len() * 2,
+        CustomError::MissingCollateralAccounts
+    );
+
+    collateral.positions
+        .iter()
+        .zip(remaining_accounts.chunks_exact(2))
+        .map(|(position, accounts)| {
+            let collateral_config = load_collateral_config(&accounts[0])?;
+            require_keys_eq!(collateral_config.market, collateral.market, CustomError::MarketMismatch);
+            require_keys_eq!(collateral_config.mint, position.mint, CustomError::CollateralMintMismatch);
+            Ok(collateral_config)
+        })
+        .collect()
+}
+
+pub fn load_collateral_config(config_account: &AccountInfo) -> Result<CollateralConfig> {
+    // Registry entries are only ever created by my admin instructions, so the owner and discriminator are enough
+    require_keys_eq!(*config_account.owner, crate::ID, CustomError::MissingCollateralAccounts);
+    let data = config_account.try_borrow_data()?;
+    CollateralConfig::try_deserialize(&mut &data[..])
+}
+
+fn load_asset_price(
+    collateral_config: &CollateralConfig,
+    price_account: &AccountInfo,
+    oracle_config: &OracleConfig,
+    target_price: Option<&ValidatedPrice>,
+) -> Result<AssetPrice> {
+    let mint = &collateral_config.mint;
+
+    // I only read the price account pinned in the registry entry
+    let pricing = match collateral_config.kind {
+        CollateralKind::StakePoolToken => {
+            require_keys_eq!(price_account.key(), collateral_config.price_account, CustomError::InvalidStakePool);
+            let exchange_rate = StakePoolExchangeRate::from_account_info(price_account)?;
+            require_keys_eq!(exchange_rate.pool_mint, *mint, CustomError::InvalidStakePool);
+            AssetPricing::StakePool(exchange_rate)
         }
+        CollateralKind::OracleToken => {
+            require_keys_eq!(price_account.key(), collateral_config.price_account, CustomError::InvalidPriceAccount);
+            let price_source = PythPriceSource::from_account_info_for_feed(price_account, collateral_config.feed_id)?;
+            let price = get_validated_price(&price_source, oracle_config)?;
+            AssetPricing::Oracle {
+                price: match target_price {
+                    Some(target_price) => price.in_currency(target_price)?,
+                    None => price,
+                },
+                decimals: collateral_config.decimals,
+            }
+        }
+    };
+
+    Ok(AssetPrice {
+        mint: *mint,
+        liquidation_threshold: collateral_config.liquidation_threshold,
+        liquidation_bonus: collateral_config.liquidation_bonus,
+        emode_category: collateral_config.emode_category,
+        pricing,
+    })
+}
+
+// The vault's e-mode category, which has to be provided whenever the vault has opted in
+pub fn load_emode_category<'a>(
+    collateral: &Collateral,
+    emode_category: Option<&'a EModeCategory>,
+) -> Result<Option<&'a EModeCategory>> {
+    if collateral.emode_category == NO_EMODE_CATEGORY {
+        return Ok(None);
+    }
+    emode_category
+        .filter(|category| category.id == collateral.emode_category)
+        .map(Some)
+        .ok_or(error!(CustomError::MissingEModeCategory))
+}
+
+// SOL and native stake are judged by the e-mode category's parameters when it includes them
+pub fn sol_emode(emode: Option<&EModeCategory>) -> Option<&EModeCategory> {
+    emode.filter(|category| category.includes_sol)
+}
+
+// A vault can only be in e-mode while every asset it holds belongs to the category
+pub fn check_emode_membership(
+    collateral: &Collateral,
+    sol_lamports: u64,
+    category: &EModeCategory,
+    position_configs: &[CollateralConfig],
+) -> Result<()> {
+    require!(
+        category.includes_sol || (sol_lamports == 0 && collateral.stake_lamports == 0),
+        CustomError::EModeCategoryMismatch
+    );
+    require!(
+        position_configs.iter().all(|config| config.emode_category == category.id),
+        CustomError::EModeCategoryMismatch
+    );
+    Ok(())
+}
+
+// I add deposited tokens to the vault's position in that mint, opening one if needed
+pub fn add_to_position(collateral: &mut Collateral, mint: Pubkey, amount: u64) -> Result<()> {
+    if let Some(position) = collateral.positions.iter_mut().find(|position| position.mint == mint) {
+        position.amount = position.amount
+            .checked_add(amount)
+            .ok_or(error!(CustomError::MathOverflow))?;
+        return Ok(());
     }
+
+    require!(
+        collateral.positions.len() < MAX_COLLATERAL_POSITIONS,
+        CustomError::TooManyCollateralPositions
+    );
+    collateral.positions.push(CollateralPosition { mint, amount });
+    Ok(())
+}
+
+// I take tokens out of the vault's position in that mint, closing it once it's empty
+pub fn remove_from_position(collateral: &mut Collateral, mint: &Pubkey, amount: u64) -> Result<()> {
+    let position = collateral.positions
+        .iter_mut()
+        .find(|position| position.mint == *mint)
+        .ok_or(error!(CustomError::CollateralNotHeld))?;
+    position.amount = position.amount
+        .checked_sub(amount)
+        .ok_or(error!(CustomError::CollateralNotHeld))?;
+    collateral.positions.retain(|position| position.amount > 0);
+    Ok(())
+}
+
+pub fn read_stake_state(stake_account: &AccountInfo) -> Result<StakeStateV2> {
+    // I only read accounts that really belong to the native stake program
+    require_keys_eq!(*stake_account.owner, stake::program::ID, CustomError::InvalidStakeAccount);
+    let data = stake_account.try_borrow_data()?;
+    StakeStateV2::deserialize(&mut &data[..]).map_err(|_| error!(CustomError::InvalidStakeAccount))
 }
 
-fn calculate_price_deviation(price1: u64, price2: u64) -> u64 {
+fn calculate_price_deviation(price1: u64, price2: u64) -> Result<u64> {
     // I can't divide by zero, so I handle that case specially
     if price1 == 0 || price2 == 0 {
-        return 100; // I consider this maximum deviation
+        return Ok(100); // I consider this maximum deviation
     }
     
     // I find the higher and lower prices to calculate the percentage difference
     let max_price = std::cmp::max(price1, price2);
     let min_price = std::cmp::min(price1, price2);
     
-    // I calculate how much the prices differ as a percentage
-    ((max_price - min_price) * 100) / min_price
+    // I calculate how much the prices differ as a percentage, rounding up to stay cautious
+    mul_div_ceil(max_price - min_price, 100, min_price)
 }
 
-fn get_price_from_feed(feed_id_hex: &str, price_feed: &Account<PriceUpdateV2>) -> Result<u64> {
-    // I convert the hex feed ID to the format Pyth expects
-    let feed_id = get_feed_id_from_hex(feed_id_hex)?;
+fn get_price_from_feed(price: Result<OraclePrice>, oracle_config: &OracleConfig) -> Result<ValidatedPrice> {
+    // The adapter already made sure the price isn't too old - stale prices are dangerous
+    let price = price?;
+    
+    // I need positive prices to work with
+    require!(price.price > 0 && price.ema_price > 0, CustomError::InvalidPrice);
+    
+    // I bring everything to my internal precision so every feed is treated the same
+    let spot_price = normalize_price(price.price as u64, price.exponent)?;
+    let spot_conf = rescale_to_price_decimals(price.conf, price.exponent)?;
+    let ema_price = normalize_price(price.ema_price as u64, price.exponent)?;
+    let ema_conf = rescale_to_price_decimals(price.ema_conf, price.exponent)?;
+    
+    // I reject prices the oracle itself isn't sure about
+    check_confidence(spot_price, spot_conf, oracle_config)?;
+    check_confidence(ema_price, ema_conf, oracle_config)?;
     
-    // I make sure the price isn't too old - stale prices are dangerous
-    let price = price_feed.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &feed_id)?;
+    // I widen each price by k confidence intervals, rounding the band outwards
+    let spot_band = mul_div_ceil(spot_conf, oracle_config.confidence_multiplier_bps, BPS_DIVISOR as u64)?;
+    let ema_band = mul_div_ceil(ema_conf, oracle_config.confidence_multiplier_bps, BPS_DIVISOR as u64)?;
     
-    // I need a positive price to work with
-    require!(price.price > 0, CustomError::InvalidPrice);
+    // Both bounds on each side have to exist before I pick the conservative one, or I'd fall back on the other
+    let collateral_price = spot_price
+        .checked_sub(spot_band)
+        .ok_or(error!(CustomError::InvalidPrice))?
+        .min(ema_price.checked_sub(ema_band).ok_or(error!(CustomError::InvalidPrice))?);
+    require!(collateral_price > 0, CustomError::InvalidPrice);
+    let debt_price = spot_price
+        .checked_add(spot_band)
+        .ok_or(error!(CustomError::MathOverflow))?
+        .max(ema_price.checked_add(ema_band).ok_or(error!(CustomError::MathOverflow))?);
     
-    // I check how confident the oracle is in this price
-    let confidence_percentage = (price.conf as u64 * 100) / price.price as u64;
-    if confidence_percentage < ORACLE_CONFIDENCE_THRESHOLD as u64 {
-        msg!("Warning: Low price confidence: {}%", confidence_percentage);
+    Ok(ValidatedPrice {
+        spot_price,
+        collateral_price,
+        debt_price,
+        publish_time: price.publish_time,
+        is_fallback: false,
+    })
+}
+
+fn check_confidence(price: u64, conf: u64, oracle_config: &OracleConfig) -> Result<()> {
+    // The wider the confidence interval relative to the price, the less I trust it
+    let confidence_ratio_bps = mul_div_ceil(conf, BPS_DIVISOR as u64, price)?;
+    if confidence_ratio_bps > oracle_config.max_confidence_ratio_bps {
+        msg!("Warning: Oracle confidence interval too wide: {} bps", confidence_ratio_bps);
         return Err(error!(CustomError::LowOracleConfidence));
     }
-    
-    Ok(price.price as u64)
+    Ok(())
+}
+
+pub fn normalize_price(price: u64, exponent: i32) -> Result<u64> {
+    let normalized = rescale_to_price_decimals(price, exponent)?;
+    require!(normalized > 0, CustomError::InvalidPrice);
+    Ok(normalized)
+}
+
+fn rescale_to_price_decimals(value: u64, exponent: i32) -> Result<u64> {
+    // I refuse feeds with an exponent I haven't planned for rather than guess
+    require!(
+        (MIN_PRICE_EXPONENT..=MAX_PRICE_EXPONENT).contains(&exponent),
+        CustomError::UnsupportedPriceExponent
+    );
+
+    // A value of v * 10^exponent becomes v * 10^(exponent + PRICE_DECIMALS) in my precision
+    let shift = exponent + PRICE_DECIMALS as i32;
+    let rescaled = if shift >= 0 {
+        (value as u128).checked_mul(10u128.pow(shift as u32))
+    } else {
+        Some(value as u128 / 10u128.pow(shift.unsigned_abs()))
+    };
+
+    rescaled
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or(error!(CustomError::InvalidPrice))
 }
 
-pub fn get_usd_value(amount_in_lamports: u64, price_feed: &Account<PriceUpdateV2>) -> Result<u64> {
+pub fn get_usd_value(amount_in_lamports: u64, price: &ValidatedPrice) -> Result<u64> {
     // I use my enhanced validation for all USD conversions
-    get_validated_usd_value(amount_in_lamports, price_feed)
+    get_validated_usd_value(amount_in_lamports, price)
 }
 
-pub fn get_lamports_from_usd(amount_in_usd: &u64, price_feed: &Account<PriceUpdateV2>) -> Result<u64> {
+pub fn get_lamports_from_usd(amount_in_usd: &u64, price: &ValidatedPrice) -> Result<u64> {
     // I don't allow minting beyond my safety limit
     require!(*amount_in_usd <= MAX_MINT_AMOUNT, CustomError::ExcessiveMintAmount);
     
-    // I get the current price from my primary oracle
-    let primary_price = get_price_from_feed(FEED_ID, price_feed)?;
-    let price_in_usd = primary_price as u128 * PRICE_FEED_DECIMAL_ADJUSTMENT;
-    
-    // I convert the USD amount to lamports based on current price
-    let amount_in_lamports = (*amount_in_usd as u128 * (LAMPORTS_PER_SOL as u128)) / price_in_usd;
-    Ok(amount_in_lamports as u64)
+    // I convert debt at the high end of the oracle's range, rounding down,
+    // so nobody walks away with more collateral than the debt they covered
+    mul_div_floor(*amount_in_usd, LAMPORTS_PER_SOL, price.debt_price)
 }
 
 pub fn calculate_fee(amount: u64, fee_bps: u16) -> Result<u64> {
-    // I calculate fees in basis points (1/100th of a percent)
-    let fee = (amount as u128 * fee_bps as u128) / BPS_DIVISOR as u128;
-    
-    // I make sure the fee doesn't overflow
-    if fee > u64::MAX as u128 {
-        return Err(error!(CustomError::FeeCalculationError));
+    // I calculate fees in basis points (1/100th of a percent), rounding up in the protocol's favour
+    mul_div_ceil(amount, fee_bps as u64, BPS_DIVISOR as u64)
+        .map_err(|_| error!(CustomError::FeeCalculationError))
+}
+// Creates a program-owned PDA partway through an instruction, for accounts only some of its paths need, topping up
+// whatever rent is already there like Anchor's init does
+pub fn create_program_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let shortfall = Rent::get()?.minimum_balance(space).saturating_sub(account.lamports());
+    if shortfall > 0 {
+        transfer(
+            CpiContext::new(system_program.clone(), Transfer { from: payer.clone(), to: account.clone() }),
+            shortfall,
+        )?;
     }
-    
-    Ok(fee as u64)
-}
\ No newline at end of file
+    allocate(
+        CpiContext::new_with_signer(system_program.clone(), Allocate { account_to_allocate: account.clone() }, signer_seeds),
+        space as u64,
+    )?;
+    assign(
+        CpiContext::new_with_signer(system_program.clone(), Assign { account_to_assign: account.clone() }, signer_seeds),
+        &crate::ID,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_DEBT_TIERS;
+
+    fn tier(min_debt: u64, threshold_haircut_bps: u64, extra_collateral_ratio: u64) -> DebtTier {
+        DebtTier { min_debt, threshold_haircut_bps, extra_collateral_ratio }
+    }
+
+    fn invalid() -> Option<Error> {
+        Some(CustomError::InvalidParameter.into())
+    }
+
+    #[test]
+    fn accepts_tiers_that_tighten_with_debt_in_any_slot_order() {
+        let tiers = [tier(0, 9_000, 100), tier(10_000_000, 1_000, 20), tier(1_000_000, 500, 10), tier(10_000_000_000, 1_000, 50)];
+        assert!(validate_debt_tiers(&tiers).is_ok());
+        assert!(validate_debt_tiers(&[DebtTier::default(); MAX_DEBT_TIERS]).is_ok());
+    }
+
+    #[test]
+    fn rejects_bigger_vaults_getting_looser_terms() {
+        let looser_haircut = [tier(1_000_000, 1_000, 10), tier(10_000_000, 500, 20)];
+        assert_eq!(validate_debt_tiers(&looser_haircut).err(), invalid());
+
+        let looser_ratio = [tier(10_000_000, 1_000, 10), tier(1_000_000, 500, 20)];
+        assert_eq!(validate_debt_tiers(&looser_ratio).err(), invalid());
+
+        let same_threshold = [tier(1_000_000, 500, 10), tier(1_000_000, 1_000, 20)];
+        assert_eq!(validate_debt_tiers(&same_threshold).err(), invalid());
+    }
+
+    #[test]
+    fn rejects_tiers_that_wipe_out_collateral_or_minting() {
+        assert_eq!(validate_debt_tiers(&[tier(1_000_000, BPS_DIVISOR as u64, 0)]).err(), invalid());
+        assert_eq!(
+            validate_debt_tiers(&[tier(1_000_000, 0, MAX_DEBT_TIER_EXTRA_COLLATERAL_RATIO + 1)]).err(),
+            invalid()
+        );
+        assert!(validate_debt_tiers(&[tier(1_000_000, 0, MAX_DEBT_TIER_EXTRA_COLLATERAL_RATIO)]).is_ok());
+    }
+
+    fn circuit_breaker(max_price_move_bps: u64, price_move_window: i64) -> (OracleState, OracleConfig) {
+        let oracle_state = OracleState {
+            last_price: 0,
+            last_price_timestamp: 0,
+            reference_price: 0,
+            reference_timestamp: 0,
+            circuit_breaker_tripped: false,
+            tripped_at: 0,
+            fallback_mode: false,
+            bump: 0,
+        };
+        let oracle_config = OracleConfig {
+            source: crate::OracleSource::Fixed,
+            pyth_price_account: Pubkey::default(),
+            switchboard_feed: Pubkey::default(),
+            fixed_price: 0,
+            fixed_exponent: 0,
+            target_price_account: Pubkey::default(),
+            target_feed_id: [0; 32],
+            max_confidence_ratio_bps: 0,
+            confidence_multiplier_bps: 0,
+            max_price_move_bps,
+            price_move_window,
+            bump: 0,
+        };
+        (oracle_state, oracle_config)
+    }
+
+    // Feeds spot prices published at the given times through the circuit breaker
+    fn record(oracle_state: &mut OracleState, oracle_config: &OracleConfig, prices: &[(u64, i64)]) {
+        for &(spot_price, publish_time) in prices {
+            let price = ValidatedPrice {
+                spot_price,
+                collateral_price: spot_price,
+                debt_price: spot_price,
+                publish_time,
+                is_fallback: false,
+            };
+            record_oracle_price(oracle_state, oracle_config, &price).unwrap();
+        }
+    }
+
+    const T: i64 = 1_700_000_000;
+
+    #[test]
+    fn trips_on_a_move_beyond_the_limit_within_the_window() {
+        let (mut oracle_state, oracle_config) = circuit_breaker(1_000, 3600);
+        record(&mut oracle_state, &oracle_config, &[(100_000, T), (105_000, T + 1800), (110_000, T + 3000)]);
+        assert!(!oracle_state.circuit_breaker_tripped);
+
+        record(&mut oracle_state, &oracle_config, &[(110_001, T + 3600)]);
+        assert!(oracle_state.circuit_breaker_tripped);
+        assert_eq!(oracle_state.tripped_at, T + 3600);
+    }
+
+    #[test]
+    fn trips_on_a_move_that_crosses_the_window_boundary() {
+        let (mut oracle_state, oracle_config) = circuit_breaker(1_000, 3600);
+        record(&mut oracle_state, &oracle_config, &[(100_000, T), (100_000, T + 3599)]);
+
+        // The window ends between these two prices, but the jump is still measured from before it
+        record(&mut oracle_state, &oracle_config, &[(120_000, T + 3601)]);
+        assert!(oracle_state.circuit_breaker_tripped);
+        assert_eq!(oracle_state.reference_timestamp, T + 3599);
+    }
+
+    #[test]
+    fn trips_on_a_move_after_a_gap_in_updates() {
+        let (mut oracle_state, oracle_config) = circuit_breaker(1_000, 3600);
+        record(&mut oracle_state, &oracle_config, &[(100_000, T), (80_000, T + 10 * 3600)]);
+        assert!(oracle_state.circuit_breaker_tripped);
+
+        // Once it's cleared, I measure from the price it tripped on
+        clear_circuit_breaker(&mut oracle_state);
+        record(&mut oracle_state, &oracle_config, &[(85_000, T + 10 * 3600 + 60)]);
+        assert!(!oracle_state.circuit_breaker_tripped);
+    }
+
+    fn feed(price: i64, conf: u64, ema_price: i64, ema_conf: u64) -> Result<OraclePrice> {
+        Ok(OraclePrice { price, conf, exponent: -9, publish_time: T, ema_price, ema_conf })
+    }
+
+    #[test]
+    fn bounds_take_the_conservative_side_of_spot_and_ema() {
+        let (_, mut oracle_config) = circuit_breaker(1_000, 3600);
+        oracle_config.max_confidence_ratio_bps = 500;
+        oracle_config.confidence_multiplier_bps = 20_000;
+
+        let price = get_price_from_feed(feed(100_000_000_000, 1_000_000_000, 98_000_000_000, 3_000_000_000), &oracle_config).unwrap();
+        assert_eq!(price.spot_price, 100_000_000_000);
+        assert_eq!(price.collateral_price, 92_000_000_000);
+        assert_eq!(price.debt_price, 104_000_000_000);
+    }
+
+    #[test]
+    fn rejects_a_band_that_swallows_either_bound() {
+        let (_, mut oracle_config) = circuit_breaker(1_000, 3600);
+        oracle_config.max_confidence_ratio_bps = BPS_DIVISOR as u64;
+        oracle_config.confidence_multiplier_bps = 20_000;
+
+        let price = get_price_from_feed(feed(100_000_000_000, 1_000_000_000, 98_000_000_000, 98_000_000_000), &oracle_config);
+        assert_eq!(price.err(), Some(CustomError::InvalidPrice.into()));
+    }
+}
@@ -1,22 +1,58 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{Mint, Token2022, TokenAccount};
-use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use anchor_spl::{stake::Stake, token_interface::{Mint, Token2022, TokenAccount, TokenInterface}};
 use crate::{
-    Collateral, Config, CustomError, 
-    calculate_health_factor, get_lamports_from_usd, withdraw_sol, burn_tokens, 
-    SEED_CONFIG_ACCOUNT, LIQUIDATION_FEE_BPS, calculate_fee
+    Collateral, Config, CustomError, Decimal, EModeCategory, OracleConfig, OracleState, mul_div_floor,
+    load_price_for_operation, load_asset_prices, load_emode_category, sol_emode, find_asset_price, remove_from_position, value_collateral, RiskProfile,
+    calculate_health_factor, get_lamports_from_usd, withdraw_sol, withdraw_token, burn_tokens,
+    split_stake, withdraw_stake, deactivate_withdrawn_stake,
+    SEED_CONFIG_ACCOUNT, SEED_EMODE_CATEGORY, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE, LIQUIDATION_FEE_BPS,
+    calculate_fee
 };
 
+// The collateral a liquidator wants to be paid in
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollateralAsset {
+    // The vault's SOL
+    Sol,
+
+    // A deactivating split of the vault's native stake account
+    Stake,
+
+    // One of the registered tokens the vault holds, by mint
+    Token(Pubkey),
+}
+
 #[derive(Accounts)]
 pub struct Liquidate<'info>{
     #[account(mut)]
     pub liquidator: Signer<'info>,
 
-    pub price_update: Account<'info, PriceUpdateV2>,
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: UncheckedAccount<'info>,
+
+    // Only needed in markets that aren't denominated in USD
+    /// CHECK: I validate this against the currency feed pinned in the oracle config
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
+    // I need the oracle config to know which price adapter to use
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Box<Account<'info, OracleConfig>>,
+
+    // I record every accepted price here for the circuit breaker
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Box<Account<'info, OracleState>>,
 
     #[account(
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
         bump = config_account.bump,
+        mut,
         has_one = mint_account,
     )]
     pub config_account: Account<'info, Config>,
@@ -24,9 +60,17 @@ pub struct Liquidate<'info>{
     #[account(
         mut,
         has_one = sol_account,
+        constraint = collateral_account.market == config_account.key() @ CustomError::MarketMismatch,
     )]
     pub collateral_account: Account<'info, Collateral>,
 
+    // Only needed when the vault has opted into an e-mode category
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[collateral_account.emode_category]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+
     #[account(mut)]
     pub sol_account: SystemAccount<'info>,
 
@@ -47,20 +91,72 @@ pub struct Liquidate<'info>{
     pub token_program: Program<'info, Token2022>,
     
     pub system_program: Program<'info, System>,
+
+    // Only needed when the liquidator is paid in one of the vault's tokens
+    pub collateral_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    #[account(
+        mut,
+        constraint = collateral_vault.owner == sol_account.key() @ CustomError::MissingCollateralAccounts,
+    )]
+    pub collateral_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(mut)]
+    pub liquidator_collateral_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub collateral_token_program: Option<Interface<'info, TokenInterface>>,
+
+    // Only needed when the liquidator is paid in stake split off the vault's stake account
+    /// CHECK: This has to be the stake account recorded on the vault
+    #[account(mut, address = collateral_account.stake_account @ CustomError::InvalidStakeAccount)]
+    pub stake_account: Option<UncheckedAccount<'info>>,
+
+    // A fresh, rent-funded account the liquidator wants the seized stake split into
+    #[account(mut)]
+    pub liquidator_split_stake_account: Option<Signer<'info>>,
+
+    pub stake_program: Option<Program<'info, Stake>>,
+    pub clock: Option<Sysvar<'info, Clock>>,
 }
 
-pub fn process_liquidate(ctx: Context<Liquidate>, amount_to_burn: u64) -> Result <()> {
+pub fn process_liquidate(ctx: Context<Liquidate>, amount_to_burn: u64, asset: CollateralAsset) -> Result <()> {
+    // I read and validate the oracle price once up front
+    let price = load_price_for_operation(
+        RiskProfile::Liquidation,
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &mut ctx.accounts.collateral_account,
+        Some(ctx.accounts.price_update.as_ref()),
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+    )?
+    .ok_or(error!(CustomError::MissingPriceAccount))?;
+
+    // Every token in the vault counts towards the position at its own price, or its e-mode category's terms
+    let emode = load_emode_category(
+        &ctx.accounts.collateral_account,
+        ctx.accounts.emode_category.as_deref().map(|category| &**category),
+    )?;
+    let asset_prices = load_asset_prices(
+        &ctx.accounts.collateral_account,
+        &ctx.accounts.oracle_config,
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+        ctx.remaining_accounts,
+    )?;
+
     // First I check if this position is actually unhealthy and needs liquidation
-    let health_factor = calculate_health_factor(
-        ctx.accounts.collateral_account.lamport_balance,
+    let valuation = value_collateral(
         &ctx.accounts.collateral_account,
         &ctx.accounts.config_account,
-        &ctx.accounts.price_update,
+        emode,
+        ctx.accounts.collateral_account.lamport_balance,
+        &price,
+        &asset_prices,
     )?;
+    let health_factor = calculate_health_factor(&valuation, &ctx.accounts.collateral_account, &ctx.accounts.config_account)?;
 
     // I only allow liquidation of positions that are actually at risk
     require!(
-        health_factor < ctx.accounts.config_account.min_health_factor,
+        health_factor < Decimal::from_bps(ctx.accounts.config_account.min_health_factor_bps),
         CustomError::AboveMinHealthFactor,
     );
 
@@ -72,51 +168,143 @@ pub fn process_liquidate(ctx: Context<Liquidate>, amount_to_burn: u64) -> Result
         amount_to_burn
     );
 
-    // I convert the stablecoin amount to SOL value using current prices
-    let lamports = get_lamports_from_usd(&amount_to_burn, &ctx.accounts.price_update)?;
+    // I convert the stablecoin amount into the chosen collateral, and use the bonus set for that asset
+    let (amount, bonus_percent) = match asset {
+        CollateralAsset::Sol | CollateralAsset::Stake => (
+            get_lamports_from_usd(&amount_to_burn, &price)?,
+            sol_emode(emode).map_or(ctx.accounts.config_account.liquidation_bonus, |category| category.liquidation_bonus),
+        ),
+        CollateralAsset::Token(mint) => {
+            let asset_price = find_asset_price(&asset_prices, &mint)?;
+            (
+                asset_price.amount_for_usd(amount_to_burn, &price)?,
+                asset_price.emode(emode).map_or(asset_price.liquidation_bonus, |category| category.liquidation_bonus),
+            )
+        }
+    };
     
     // I give liquidators a bonus to incentivize them to help maintain system health
-    let liquidation_bonus = lamports * ctx.accounts.config_account.liquidation_bonus / 100;
+    let liquidation_bonus = mul_div_floor(amount, bonus_percent, 100)?;
     
     // I also collect a small fee for the protocol treasury
-    let protocol_fee = calculate_fee(lamports, LIQUIDATION_FEE_BPS)?;
+    let protocol_fee = calculate_fee(amount, LIQUIDATION_FEE_BPS)?;
     
     // The liquidator gets the collateral plus bonus, minus the protocol fee
-    let amount_to_liquidate = lamports + liquidation_bonus - protocol_fee;
+    let amount_to_liquidate = amount
+        .checked_add(liquidation_bonus)
+        .and_then(|amount| amount.checked_sub(protocol_fee))
+        .ok_or(error!(CustomError::MathOverflow))?;
     
     // I log all the economics for transparency
     msg!(
-        "Liquidation economics: Collateral={}, Bonus={}, Fee={}, Total={}",
-        lamports,
+        "Liquidation economics: Asset={:?}, Collateral={}, Bonus={}, Fee={}, Total={}",
+        asset,
+        amount,
         liquidation_bonus,
         protocol_fee,
         amount_to_liquidate
     );
 
-    // I transfer the SOL collateral to the liquidator
-    withdraw_sol(
-        ctx.accounts.collateral_account.bump_sol_account,
-        &ctx.accounts.collateral_account.depositer,
-        &ctx.accounts.system_program,
-        &ctx.accounts.sol_account,
-        &ctx.accounts.liquidator.to_account_info(),
-        amount_to_liquidate,
-    )?;
+    match asset {
+        CollateralAsset::Sol => {
+            // I keep the protocol fee back when there's somewhere to send it
+            let reserved_fee = if ctx.accounts.fee_recipient.is_some() { protocol_fee } else { 0 };
+            require!(
+                amount_to_liquidate.saturating_add(reserved_fee) <= ctx.accounts.sol_account.lamports(),
+                CustomError::CollateralNotHeld
+            );
+            withdraw_sol(
+                &ctx.accounts.collateral_account,
+                &ctx.accounts.system_program,
+                &ctx.accounts.sol_account,
+                &ctx.accounts.liquidator.to_account_info(),
+                amount_to_liquidate,
+            )?;
+
+            // If there's a fee recipient configured, I send the fee there
+            if let Some(fee_recipient) = &ctx.accounts.fee_recipient {
+                withdraw_sol(
+                    &ctx.accounts.collateral_account,
+                    &ctx.accounts.system_program,
+                    &ctx.accounts.sol_account,
+                    fee_recipient,
+                    protocol_fee,
+                )?;
+                
+                msg!("Protocol fee of {} lamports collected", protocol_fee);
+            } else {
+                msg!("No fee recipient provided, fee remains in collateral account");
+            }
+        }
+        CollateralAsset::Stake => {
+            // I split the seized lamports off the vault's stake account, already deactivating, for the liquidator
+            let (Some(stake_account), Some(split_stake_account), Some(stake_program), Some(clock)) = (
+                &ctx.accounts.stake_account,
+                &ctx.accounts.liquidator_split_stake_account,
+                &ctx.accounts.stake_program,
+                &ctx.accounts.clock,
+            ) else {
+                return err!(CustomError::MissingCollateralAccounts);
+            };
+
+            ctx.accounts.collateral_account.stake_lamports = ctx.accounts.collateral_account.stake_lamports
+                .checked_sub(amount_to_liquidate)
+                .ok_or(error!(CustomError::CollateralNotHeld))?;
+            split_stake(
+                &ctx.accounts.collateral_account,
+                stake_account,
+                split_stake_account,
+                &ctx.accounts.sol_account,
+                amount_to_liquidate,
+            )?;
+            deactivate_withdrawn_stake(
+                &ctx.accounts.collateral_account,
+                split_stake_account,
+                &ctx.accounts.sol_account,
+                clock,
+                stake_program,
+            )?;
+            withdraw_stake(
+                &ctx.accounts.collateral_account,
+                split_stake_account,
+                &ctx.accounts.sol_account,
+                &ctx.accounts.liquidator,
+                clock,
+                stake_program,
+            )?;
+
+            // The protocol fee stays behind in the vault's stake
+            msg!("Seized {} lamports of stake into {}", amount_to_liquidate, split_stake_account.key());
+        }
+        CollateralAsset::Token(mint) => {
+            let (Some(collateral_mint), Some(collateral_vault), Some(liquidator_collateral_account), Some(collateral_token_program)) = (
+                &ctx.accounts.collateral_mint,
+                &ctx.accounts.collateral_vault,
+                &ctx.accounts.liquidator_collateral_account,
+                &ctx.accounts.collateral_token_program,
+            ) else {
+                return err!(CustomError::MissingCollateralAccounts);
+            };
 
-    // If there's a fee recipient configured, I send the fee there
-    if let Some(fee_recipient) = &ctx.accounts.fee_recipient {
-        withdraw_sol(
-            ctx.accounts.collateral_account.bump_sol_account,
-            &ctx.accounts.collateral_account.depositer,
-            &ctx.accounts.system_program,
-            &ctx.accounts.sol_account,
-            fee_recipient,
-            protocol_fee,
-        )?;
-        
-        msg!("Protocol fee of {} lamports collected", protocol_fee);
-    } else {
-        msg!("No fee recipient provided, fee remains in collateral account");
+            // Every token account has to be for the asset the liquidator chose
+            require_keys_eq!(collateral_mint.key(), mint, CustomError::CollateralMintMismatch);
+            require_keys_eq!(collateral_vault.mint, mint, CustomError::CollateralMintMismatch);
+            require_keys_eq!(liquidator_collateral_account.mint, mint, CustomError::CollateralMintMismatch);
+
+            remove_from_position(&mut ctx.accounts.collateral_account, &mint, amount_to_liquidate)?;
+            withdraw_token(
+                &ctx.accounts.collateral_account,
+                collateral_token_program,
+                collateral_mint,
+                collateral_vault,
+                liquidator_collateral_account,
+                &ctx.accounts.sol_account,
+                amount_to_liquidate,
+            )?;
+
+            // The protocol fee stays behind in the vault's token position
+            msg!("Seized {} tokens of {}", amount_to_liquidate, mint);
+        }
     }
 
     // I burn the stablecoins that the liquidator is using to purchase the collateral
@@ -130,21 +318,29 @@ pub fn process_liquidate(ctx: Context<Liquidate>, amount_to_burn: u64) -> Result
 
     // I update the collateral account to reflect the new balances
     ctx.accounts.collateral_account.lamport_balance = ctx.accounts.sol_account.lamports();
-    ctx.accounts.collateral_account.amount_minted -= amount_to_burn;
+    ctx.accounts.collateral_account.amount_minted = ctx.accounts.collateral_account.amount_minted
+        .checked_sub(amount_to_burn)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    ctx.accounts.config_account.vault_debt = ctx.accounts.config_account.vault_debt
+        .checked_sub(amount_to_burn)
+        .ok_or(error!(CustomError::MathOverflow))?;
 
     // I verify that the position is healthier after liquidation
-    let new_health_factor = calculate_health_factor(
-        ctx.accounts.collateral_account.lamport_balance,
+    let new_valuation = value_collateral(
         &ctx.accounts.collateral_account,
         &ctx.accounts.config_account,
-        &ctx.accounts.price_update
+        emode,
+        ctx.accounts.collateral_account.lamport_balance,
+        &price,
+        &asset_prices,
     )?;
+    let new_health_factor = calculate_health_factor(&new_valuation, &ctx.accounts.collateral_account, &ctx.accounts.config_account)?;
     
     // I log the final state for transparency
     msg!(
-        "Liquidation complete: New Health Factor={}, Remaining Collateral={}, Remaining Debt={}",
+        "Liquidation complete: New Health Factor={}, Remaining Collateral Value={}, Remaining Debt={}",
         new_health_factor,
-        ctx.accounts.collateral_account.lamport_balance,
+        new_valuation.total_value,
         ctx.accounts.collateral_account.amount_minted
     );
 
@@ -1,7 +1,11 @@
 pub use redeem_collateral_and_burn_tokens::*;
 pub use utils::*;
 pub use liquidate::*;
+pub use withdraw_token_collateral::*;
+pub use withdraw_stake_collateral::*;
 
 pub mod redeem_collateral_and_burn_tokens;
 pub mod utils;
-pub mod liquidate;
\ No newline at end of file
+pub mod liquidate;
+pub mod withdraw_token_collateral;
+pub mod withdraw_stake_collateral;
\ No newline at end of file
@@ -1,7 +1,10 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, Token2022};
-use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
-use crate::{Collateral, Config, SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, check_health_factor, burn_tokens, withdraw_sol};
+use crate::{
+    Collateral, Config, CustomError, EModeCategory, OracleConfig, OracleState,
+    SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, SEED_EMODE_CATEGORY, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE,
+    check_health_factor, load_asset_prices, load_emode_category, value_collateral, burn_tokens, withdraw_sol, load_price_for_operation, RiskProfile,
+};
 
 #[derive(Accounts)]
 pub struct RedeemCollateralAndBurnTokens<'info> {
@@ -9,13 +12,34 @@ pub struct RedeemCollateralAndBurnTokens<'info> {
     #[account(mut)]
     pub depositer: Signer<'info>,
     
-    // I need the price feed to calculate the health factor
-    pub price_update: Account<'info, PriceUpdateV2>,
+    // I need the price feed to calculate the health factor - but not for repayments
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only needed in markets that aren't denominated in USD
+    /// CHECK: I validate this against the currency feed pinned in the oracle config
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
+    // I need the oracle config to know which price adapter to use
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Box<Account<'info, OracleConfig>>,
+
+    // I record every accepted price here for the circuit breaker
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Box<Account<'info, OracleState>>,
 
     // I need the protocol configuration
     #[account(
-        seeds = [SEED_CONFIG_ACCOUNT],
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
         bump = config_account.bump,
+        mut,
         has_one = mint_account,
     )]
     pub config_account: Box<Account<'info, Config>>,
@@ -23,13 +47,20 @@ pub struct RedeemCollateralAndBurnTokens<'info> {
     // The user's collateral account that tracks their position
     #[account(
         mut,
-        seeds = [SEED_COLLATERAL_ACCOUNT, depositer.key().as_ref()],
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
         bump = collateral_account.bump,
         has_one = sol_account,
         has_one = token_account,
     )]
     pub collateral_account: Account<'info, Collateral>,
 
+    // Only needed when the vault has opted into an e-mode category
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[collateral_account.emode_category]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+
     // The SOL account holding the user's collateral
     #[account(mut)]
     pub sol_account: SystemAccount<'info>,
@@ -55,17 +86,56 @@ pub fn process_redeem_collateral_and_burn_tokens(
 ) -> Result<()> {
     // I update the collateral account to reflect the withdrawal
     let collateral_account = &mut ctx.accounts.collateral_account;
-    collateral_account.lamport_balance = ctx.accounts.sol_account.lamports() - amount_collateral;
-    collateral_account.amount_minted -= amount_to_burn;  
+    collateral_account.lamport_balance = ctx.accounts.sol_account.lamports()
+        .checked_sub(amount_collateral)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    collateral_account.amount_minted = collateral_account.amount_minted
+        .checked_sub(amount_to_burn)
+        .ok_or(error!(CustomError::MathOverflow))?;
+    ctx.accounts.config_account.vault_debt = ctx.accounts.config_account.vault_debt
+        .checked_sub(amount_to_burn)
+        .ok_or(error!(CustomError::MathOverflow))?;
 
-    // I make sure the position will still be healthy after withdrawal
-    check_health_factor(
-        ctx.accounts.collateral_account.lamport_balance,
-        &ctx.accounts.collateral_account,
-        &ctx.accounts.config_account,
-        &ctx.accounts.price_update,
+    // Withdrawing against remaining debt adds risk, repaying or emptying a repaid vault doesn't
+    let risk_profile = RiskProfile::for_position_change(
+        0,
+        amount_collateral,
+        ctx.accounts.collateral_account.amount_minted,
+    );
+    let price = load_price_for_operation(
+        risk_profile,
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &mut ctx.accounts.collateral_account,
+        ctx.accounts.price_update.as_ref().map(|account| account.as_ref()),
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
     )?;
 
+    // I make sure the position will still be healthy after withdrawal
+    if let Some(price) = price.filter(|_| risk_profile.increases_risk()) {
+        // Every token in the vault counts towards the position at its own price
+        let emode = load_emode_category(
+            &ctx.accounts.collateral_account,
+            ctx.accounts.emode_category.as_deref().map(|category| &**category),
+        )?;
+        let asset_prices = load_asset_prices(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.oracle_config,
+            ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+            ctx.remaining_accounts,
+        )?;
+        let valuation = value_collateral(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.config_account,
+            emode,
+            ctx.accounts.collateral_account.lamport_balance,
+            &price,
+            &asset_prices,
+        )?;
+
+        check_health_factor(&valuation, &ctx.accounts.collateral_account, &ctx.accounts.config_account)?;
+    }
+
     // I burn the stablecoins first to ensure the user has them
     burn_tokens(
         &ctx.accounts.token_program,
@@ -77,8 +147,7 @@ pub fn process_redeem_collateral_and_burn_tokens(
 
     // Then I release the collateral back to the user
     withdraw_sol(
-        ctx.accounts.collateral_account.bump_sol_account,
-        &ctx.accounts.depositer.key(),
+        &ctx.accounts.collateral_account,
         &ctx.accounts.system_program,
         &ctx.accounts.sol_account,
         &ctx.accounts.depositer.to_account_info(),
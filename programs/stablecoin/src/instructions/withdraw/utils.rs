@@ -1,18 +1,32 @@
-use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
-use anchor_spl::{token_2022::{burn, Burn}};
-use anchor_spl::token_interface::{Token2022, TokenAccount, Mint};
-use crate::SEED_SOL_ACCOUNT;
+use anchor_lang::{
+    prelude::*,
+    solana_program::program::invoke_signed,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    stake::{authorize, deactivate_stake, Authorize, DeactivateStake, Stake},
+    token_2022::{burn, Burn},
+};
+use solana_stake_interface::{instruction::split, state::StakeAuthorize};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, Token2022, TokenAccount, TokenInterface, TransferChecked,
+};
+use crate::{Collateral, SEED_SOL_ACCOUNT};
 
 pub fn withdraw_sol<'info>(
-    bump: u8,
-    depositer_key: &Pubkey,
+    collateral: &Collateral,
     system_program: &Program<'info, System>,
     from: &SystemAccount<'info>,
     to: &AccountInfo<'info>,
     amount: u64,
 ) -> Result<()> {
     // I create the PDA signer seeds to authorize the SOL withdrawal
-    let signer_seeds: &[&[&[u8]]] = &[&[SEED_SOL_ACCOUNT, depositer_key.as_ref(), &[bump]]];
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        SEED_SOL_ACCOUNT,
+        collateral.market.as_ref(),
+        collateral.depositer.as_ref(),
+        &[collateral.bump_sol_account],
+    ]];
 
     // I transfer the SOL from the collateral account to the recipient
     let _ = transfer(
@@ -34,21 +48,148 @@ pub fn burn_tokens<'info> (
     token_program: &Program<'info, Token2022>,
     mint_account: &InterfaceAccount<'info, Mint>,
     token_account: &InterfaceAccount<'info, TokenAccount>,
-    _authority: &Signer<'info>,
+    authority: &Signer<'info>,
     amount: u64,
 ) -> Result<()> {
-    // I burn the stablecoins to reduce the total supply
-    let _ = burn(
+    // I burn the stablecoins to reduce the total supply - the holder has to sign for their own tokens
+    burn(
         CpiContext::new(
             token_program.to_account_info(),
             Burn {
                 mint: mint_account.to_account_info(),
                 from: token_account.to_account_info(), 
-                authority: mint_account.to_account_info(), 
+                authority: authority.to_account_info(), 
             },
         ),
         amount,
-    );
+    )?;
  
     Ok(())
+}
+
+pub fn withdraw_token<'info>(
+    collateral: &Collateral,
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, Mint>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    authority: &SystemAccount<'info>,
+    amount: u64,
+) -> Result<()> {
+    // The vault's SOL account owns its collateral token accounts, so I sign with the same seeds
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        SEED_SOL_ACCOUNT,
+        collateral.market.as_ref(),
+        collateral.depositer.as_ref(),
+        &[collateral.bump_sol_account],
+    ]];
+
+    transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )?;
+
+    Ok(())
+}
+
+pub fn withdraw_stake<'info>(
+    collateral: &Collateral,
+    stake_account: &AccountInfo<'info>,
+    sol_account: &SystemAccount<'info>,
+    new_authority: &AccountInfo<'info>,
+    clock: &Sysvar<'info, Clock>,
+    stake_program: &Program<'info, Stake>,
+) -> Result<()> {
+    // The vault's SOL account holds both stake authorities, so I sign with its seeds
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        SEED_SOL_ACCOUNT,
+        collateral.market.as_ref(),
+        collateral.depositer.as_ref(),
+        &[collateral.bump_sol_account],
+    ]];
+
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        authorize(
+            CpiContext::new_with_signer(
+                stake_program.to_account_info(),
+                Authorize {
+                    stake: stake_account.to_account_info(),
+                    authorized: sol_account.to_account_info(),
+                    new_authorized: new_authority.to_account_info(),
+                    clock: clock.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            stake_authorize,
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn split_stake<'info>(
+    collateral: &Collateral,
+    stake_account: &AccountInfo<'info>,
+    split_stake_account: &AccountInfo<'info>,
+    sol_account: &SystemAccount<'info>,
+    lamports: u64,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        SEED_SOL_ACCOUNT,
+        collateral.market.as_ref(),
+        collateral.depositer.as_ref(),
+        &[collateral.bump_sol_account],
+    ]];
+
+    // The split account signs for its own allocation, and the vault signs for the split itself
+    for instruction in split(stake_account.key, sol_account.key, lamports, split_stake_account.key) {
+        invoke_signed(
+            &instruction,
+            &[
+                stake_account.to_account_info(),
+                split_stake_account.to_account_info(),
+                sol_account.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn deactivate_withdrawn_stake<'info>(
+    collateral: &Collateral,
+    stake_account: &AccountInfo<'info>,
+    sol_account: &SystemAccount<'info>,
+    clock: &Sysvar<'info, Clock>,
+    stake_program: &Program<'info, Stake>,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        SEED_SOL_ACCOUNT,
+        collateral.market.as_ref(),
+        collateral.depositer.as_ref(),
+        &[collateral.bump_sol_account],
+    ]];
+
+    // I start the cooldown so whoever receives this stake can withdraw it as SOL next epoch
+    deactivate_stake(CpiContext::new_with_signer(
+        stake_program.to_account_info(),
+        DeactivateStake {
+            stake: stake_account.to_account_info(),
+            staker: sol_account.to_account_info(),
+            clock: clock.to_account_info(),
+        },
+        signer_seeds,
+    ))
 }
\ No newline at end of file
@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+use anchor_spl::stake::Stake;
+use crate::{
+    Collateral, Config, CustomError, EModeCategory, OracleConfig, OracleState,
+    SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, SEED_EMODE_CATEGORY, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE,
+    SEED_SOL_ACCOUNT,
+    check_health_factor, load_asset_prices, load_emode_category, load_price_for_operation, value_collateral,
+    split_stake, withdraw_stake, RiskProfile,
+};
+
+#[derive(Accounts)]
+pub struct WithdrawStakeCollateral<'info> {
+    #[account(mut)]
+    pub depositer: Signer<'info>,
+
+    // I need the price feed to check the health factor - but not once the vault is repaid
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only needed in markets that aren't denominated in USD
+    /// CHECK: I validate this against the currency feed pinned in the oracle config
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Box<Account<'info, OracleConfig>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Box<Account<'info, OracleState>>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump,
+        has_one = sol_account,
+        has_one = stake_account @ CustomError::InvalidStakeAccount,
+    )]
+    pub collateral_account: Account<'info, Collateral>,
+
+    // Only needed when the vault has opted into an e-mode category
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[collateral_account.emode_category]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+
+    #[account(
+        seeds = [SEED_SOL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump_sol_account,
+    )]
+    pub sol_account: SystemAccount<'info>,
+
+    /// CHECK: This is the stake account recorded on the vault
+    #[account(mut)]
+    pub stake_account: UncheckedAccount<'info>,
+
+    // A fresh, rent-funded account to split into when only part of the stake is withdrawn
+    #[account(mut)]
+    pub split_stake_account: Option<Signer<'info>>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn process_withdraw_stake_collateral(ctx: Context<WithdrawStakeCollateral>, lamports: u64) -> Result<()> {
+    let collateral_account = &mut ctx.accounts.collateral_account;
+    require!(lamports > 0, CustomError::InvalidParameter);
+    collateral_account.stake_lamports = collateral_account.stake_lamports
+        .checked_sub(lamports)
+        .ok_or(error!(CustomError::MathOverflow))?;
+
+    // Withdrawing against remaining debt adds risk, emptying a repaid vault doesn't
+    let risk_profile = RiskProfile::for_position_change(
+        0,
+        lamports,
+        ctx.accounts.collateral_account.amount_minted,
+    );
+    let price = load_price_for_operation(
+        risk_profile,
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &mut ctx.accounts.collateral_account,
+        ctx.accounts.price_update.as_ref().map(|account| account.as_ref()),
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+    )?;
+
+    // I value what's left in the vault after the stake leaves
+    if let Some(price) = price.filter(|_| risk_profile.increases_risk()) {
+        let emode = load_emode_category(
+            &ctx.accounts.collateral_account,
+            ctx.accounts.emode_category.as_deref().map(|category| &**category),
+        )?;
+        let asset_prices = load_asset_prices(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.oracle_config,
+            ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+            ctx.remaining_accounts,
+        )?;
+        let valuation = value_collateral(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.config_account,
+            emode,
+            ctx.accounts.sol_account.lamports(),
+            &price,
+            &asset_prices,
+        )?;
+
+        check_health_factor(&valuation, &ctx.accounts.collateral_account, &ctx.accounts.config_account)?;
+    }
+
+    if ctx.accounts.collateral_account.stake_lamports == 0 {
+        // Taking everything out just hands the whole stake account back
+        withdraw_stake(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.stake_account,
+            &ctx.accounts.sol_account,
+            &ctx.accounts.depositer,
+            &ctx.accounts.clock,
+            &ctx.accounts.stake_program,
+        )?;
+        ctx.accounts.collateral_account.stake_account = Pubkey::default();
+    } else {
+        // Otherwise I split the requested lamports off into a new stake account for the user
+        let split_stake_account = ctx.accounts.split_stake_account
+            .as_ref()
+            .ok_or(error!(CustomError::MissingCollateralAccounts))?;
+        split_stake(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.stake_account,
+            split_stake_account,
+            &ctx.accounts.sol_account,
+            lamports,
+        )?;
+        withdraw_stake(
+            &ctx.accounts.collateral_account,
+            split_stake_account,
+            &ctx.accounts.sol_account,
+            &ctx.accounts.depositer,
+            &ctx.accounts.clock,
+            &ctx.accounts.stake_program,
+        )?;
+    }
+
+    msg!(
+        "Stake withdrawal successful: {} lamports withdrawn, {} remaining",
+        lamports,
+        ctx.accounts.collateral_account.stake_lamports
+    );
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{
+    Collateral, Config, EModeCategory, OracleConfig, OracleState,
+    SEED_COLLATERAL_ACCOUNT, SEED_CONFIG_ACCOUNT, SEED_EMODE_CATEGORY, SEED_ORACLE_CONFIG, SEED_ORACLE_STATE, SEED_SOL_ACCOUNT,
+    check_health_factor, load_asset_prices, load_emode_category, load_price_for_operation, remove_from_position, value_collateral,
+    withdraw_token, RiskProfile,
+};
+
+#[derive(Accounts)]
+pub struct WithdrawTokenCollateral<'info> {
+    #[account(mut)]
+    pub depositer: Signer<'info>,
+
+    // I need the price feed to check the health factor - but not once the vault is repaid
+    /// CHECK: I validate this against the adapter selected in the oracle config
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    // Only needed in markets that aren't denominated in USD
+    /// CHECK: I validate this against the currency feed pinned in the oracle config
+    pub target_price_update: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [SEED_ORACLE_CONFIG, config_account.key().as_ref()],
+        bump = oracle_config.bump,
+    )]
+    pub oracle_config: Box<Account<'info, OracleConfig>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_ORACLE_STATE, config_account.key().as_ref()],
+        bump = oracle_state.bump,
+    )]
+    pub oracle_state: Box<Account<'info, OracleState>>,
+
+    #[account(
+        seeds = [SEED_CONFIG_ACCOUNT, config_account.market_id.to_le_bytes().as_ref()],
+        bump = config_account.bump,
+    )]
+    pub config_account: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [SEED_COLLATERAL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump,
+        has_one = sol_account,
+    )]
+    pub collateral_account: Account<'info, Collateral>,
+
+    // Only needed when the vault has opted into an e-mode category
+    #[account(
+        seeds = [SEED_EMODE_CATEGORY, config_account.key().as_ref(), &[collateral_account.emode_category]],
+        bump = emode_category.bump,
+    )]
+    pub emode_category: Option<Box<Account<'info, EModeCategory>>>,
+
+    #[account(
+        seeds = [SEED_SOL_ACCOUNT, config_account.key().as_ref(), depositer.key().as_ref()],
+        bump = collateral_account.bump_sol_account,
+    )]
+    pub sol_account: SystemAccount<'info>,
+
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = sol_account,
+        associated_token::token_program = collateral_token_program,
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = collateral_mint,
+        token::token_program = collateral_token_program,
+    )]
+    pub depositer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn process_withdraw_token_collateral(ctx: Context<WithdrawTokenCollateral>, amount: u64) -> Result<()> {
+    let collateral_mint = ctx.accounts.collateral_mint.key();
+    remove_from_position(&mut ctx.accounts.collateral_account, &collateral_mint, amount)?;
+
+    // Withdrawing against remaining debt adds risk, emptying a repaid vault doesn't
+    let risk_profile = RiskProfile::for_position_change(
+        0,
+        amount,
+        ctx.accounts.collateral_account.amount_minted,
+    );
+    let price = load_price_for_operation(
+        risk_profile,
+        &ctx.accounts.oracle_config,
+        &mut ctx.accounts.oracle_state,
+        &mut ctx.accounts.collateral_account,
+        ctx.accounts.price_update.as_ref().map(|account| account.as_ref()),
+        ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+    )?;
+
+    // I value everything left in the vault, each token at its own price
+    if let Some(price) = price.filter(|_| risk_profile.increases_risk()) {
+        let emode = load_emode_category(
+            &ctx.accounts.collateral_account,
+            ctx.accounts.emode_category.as_deref().map(|category| &**category),
+        )?;
+        let asset_prices = load_asset_prices(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.oracle_config,
+            ctx.accounts.target_price_update.as_ref().map(|account| account.as_ref()),
+            ctx.remaining_accounts,
+        )?;
+        let valuation = value_collateral(
+            &ctx.accounts.collateral_account,
+            &ctx.accounts.config_account,
+            emode,
+            ctx.accounts.sol_account.lamports(),
+            &price,
+            &asset_prices,
+        )?;
+
+        check_health_factor(&valuation, &ctx.accounts.collateral_account, &ctx.accounts.config_account)?;
+    }
+
+    withdraw_token(
+        &ctx.accounts.collateral_account,
+        &ctx.accounts.collateral_token_program,
+        &ctx.accounts.collateral_mint,
+        &ctx.accounts.collateral_vault,
+        &ctx.accounts.depositer_token_account,
+        &ctx.accounts.sol_account,
+        amount,
+    )?;
+
+    msg!(
+        "Token withdrawal successful: {} of {} withdrawn, {} collateral tokens held",
+        amount,
+        collateral_mint,
+        ctx.accounts.collateral_account.positions.len()
+    );
+
+    Ok(())
+}
@@ -4,11 +4,15 @@ pub use instructions::*;
 pub use state::*;
 pub use constants::*;
 pub use error::*;
+pub use math::*;
+pub use oracle::*;
 
 pub mod instructions;
 pub mod state;
 pub mod constants; 
 pub mod error;
+pub mod math;
+pub mod oracle;
 
 declare_id!("GmTUJTroHa7jdWdtVyhuMGQH9YRgcwM5W14JGydfAW1M");
 
@@ -19,15 +23,90 @@ pub mod stablecoin {
     use instructions::governance::create_proposal::ProposalType;
     use instructions::governance::vote_on_proposal::VoteType;
 
-    // I use this to set up the initial configuration when deploying my stablecoin
-    pub fn initialize_config(mut ctx: Context<InitializeConfig>) -> Result<()> {
-        process_initialize_config(&mut ctx)?;
+    // I use this to set up a market: its own config, stablecoin mint, vaults and parameters.
+    // target_feed_id is the Pyth feed pricing the market's currency in USD, ignored for a USD market
+    pub fn initialize_config(
+        mut ctx: Context<InitializeConfig>,
+        market_id: u64,
+        target_feed_id: [u8; 32],
+    ) -> Result<()> {
+        process_initialize_config(&mut ctx, market_id, target_feed_id)?;
         Ok(())
     }
 
-    // I allow admins to update key parameters like minimum health factor
-    pub fn update_config(ctx: Context<UpdateConfig>, min_health_factor: u64) -> Result<()> {
-        process_update_config(ctx, min_health_factor)
+    // I allow admins to update key parameters like minimum health factor (in basis points)
+    pub fn update_config(ctx: Context<UpdateConfig>, min_health_factor_bps: u64) -> Result<()> {
+        process_update_config(ctx, min_health_factor_bps)
+    }
+
+    // I allow the admin to hand the market to a new admin, usually the market's governance authority PDA
+    pub fn set_authority(ctx: Context<SetAuthority>, new_authority: Pubkey) -> Result<()> {
+        process_set_authority(ctx, new_authority)
+    }
+
+    // I allow admins to choose which oracle adapter prices the collateral
+    pub fn update_oracle_config(
+        ctx: Context<UpdateOracleConfig>,
+        source: OracleSource,
+        pyth_price_account: Pubkey,
+        switchboard_feed: Pubkey,
+        fixed_price: i64,
+        fixed_exponent: i32,
+    ) -> Result<()> {
+        process_update_oracle_config(
+            ctx,
+            source,
+            pyth_price_account,
+            switchboard_feed,
+            fixed_price,
+            fixed_exponent,
+        )
+    }
+
+    // I allow admins to tune how conservatively oracle prices are applied
+    pub fn update_oracle_risk_params(
+        ctx: Context<UpdateOracleRiskParams>,
+        max_confidence_ratio_bps: u64,
+        confidence_multiplier_bps: u64,
+        max_price_move_bps: u64,
+        price_move_window: i64,
+    ) -> Result<()> {
+        process_update_oracle_risk_params(
+            ctx,
+            max_confidence_ratio_bps,
+            confidence_multiplier_bps,
+            max_price_move_bps,
+            price_move_window,
+        )
+    }
+
+    // I allow admins to clear the oracle circuit breaker once things have calmed down
+    pub fn reset_circuit_breaker(ctx: Context<ResetCircuitBreaker>) -> Result<()> {
+        process_reset_circuit_breaker(ctx)
+    }
+
+    // I allow admins to list a liquid staking token, priced through its stake pool
+    pub fn add_lst_collateral(
+        ctx: Context<AddLstCollateral>,
+        liquidation_threshold: u64,
+        liquidation_bonus: u64,
+    ) -> Result<()> {
+        process_add_lst_collateral(ctx, liquidation_threshold, liquidation_bonus)
+    }
+
+    // I allow admins to list a token priced by its own Pyth feed
+    pub fn add_token_collateral(
+        ctx: Context<AddTokenCollateral>,
+        feed_id: [u8; 32],
+        liquidation_threshold: u64,
+        liquidation_bonus: u64,
+    ) -> Result<()> {
+        process_add_token_collateral(ctx, feed_id, liquidation_threshold, liquidation_bonus)
+    }
+
+    // Anyone can push a fresh oracle price into the protocol's oracle state
+    pub fn refresh_oracle_price(ctx: Context<RefreshOraclePrice>) -> Result<()> {
+        process_refresh_oracle_price(ctx)
     }
 
     // This is how users deposit SOL collateral and mint my stablecoin
@@ -51,9 +130,69 @@ pub mod stablecoin {
     // If a position becomes undercollateralized, I allow liquidators to step in
     pub fn liquidate(
         ctx: Context<Liquidate>,
-        amount_to_burn: u64
+        amount_to_burn: u64,
+        asset: CollateralAsset,
     ) -> Result<()> {
-        process_liquidate(ctx, amount_to_burn)
+        process_liquidate(ctx, amount_to_burn, asset)
+    }
+
+    // Users can back their position with any mix of listed tokens as well as SOL
+    pub fn deposit_token_collateral(ctx: Context<DepositTokenCollateral>, amount: u64) -> Result<()> {
+        process_deposit_token_collateral(ctx, amount)
+    }
+
+    pub fn withdraw_token_collateral(ctx: Context<WithdrawTokenCollateral>, amount: u64) -> Result<()> {
+        process_withdraw_token_collateral(ctx, amount)
+    }
+
+    // Users can opt their vault into an e-mode category when everything it holds is correlated
+    pub fn set_emode_category(ctx: Context<SetEModeCategory>, category_id: u8) -> Result<()> {
+        process_set_emode_category(ctx, category_id)
+    }
+
+    // Facilitators governance has registered mint straight into their bucket, signing through CPI
+    pub fn facilitator_mint(ctx: Context<FacilitatorMint>, amount: u64) -> Result<()> {
+        process_facilitator_mint(ctx, amount)
+    }
+
+    // ...and burn back against it
+    pub fn facilitator_burn(ctx: Context<FacilitatorBurn>, amount: u64) -> Result<()> {
+        process_facilitator_burn(ctx, amount)
+    }
+
+    // Users can also back their position with a delegated stake account, so it keeps earning yield
+    pub fn deposit_stake_collateral(ctx: Context<DepositStakeCollateral>) -> Result<()> {
+        process_deposit_stake_collateral(ctx)
+    }
+
+    pub fn withdraw_stake_collateral(ctx: Context<WithdrawStakeCollateral>, lamports: u64) -> Result<()> {
+        process_withdraw_stake_collateral(ctx, lamports)
+    }
+    
+    // Voters lock governance tokens to get voting power, which is checkpointed by slot;
+    // depositing more raises the locked amount without moving the unlock time
+    pub fn deposit_governance_tokens(ctx: Context<DepositGovernanceTokens>, amount: u64) -> Result<()> {
+        process_deposit_governance_tokens(ctx, amount)
+    }
+
+    // Longer locks vote with more weight, decaying linearly until they unlock
+    pub fn extend_lock(ctx: Context<ExtendLock>, lock_duration: i64) -> Result<()> {
+        process_extend_lock(ctx, lock_duration)
+    }
+
+    // Voters can hand their voting power to a delegate, and take it back, for proposals created afterwards
+    pub fn delegate(ctx: Context<Delegate>, delegate: Pubkey) -> Result<()> {
+        process_delegate(ctx, delegate)
+    }
+
+    pub fn undelegate(ctx: Context<Undelegate>) -> Result<()> {
+        process_undelegate(ctx)
+    }
+
+    // Tokens come back out once the lock ends (or after an emergency shutdown);
+    // proposals already snapshotted keep the old balance
+    pub fn withdraw_governance_tokens(ctx: Context<WithdrawGovernanceTokens>, amount: u64) -> Result<()> {
+        process_withdraw_governance_tokens(ctx, amount)
     }
     
     // I've added governance so the community can propose changes
@@ -67,14 +206,30 @@ pub mod stablecoin {
     }
     
     // Token holders can vote on governance proposals
-    pub fn vote_on_proposal(
-        ctx: Context<VoteOnProposal>,
+    pub fn vote_on_proposal<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VoteOnProposal<'info>>,
         proposal_id: u64,
         vote_type: VoteType
     ) -> Result<()> {
         process_vote_on_proposal(ctx, proposal_id, vote_type)
     }
     
+    // Voters can take their vote back while voting is still open
+    pub fn withdraw_vote(
+        ctx: Context<WithdrawVote>,
+        proposal_id: u64
+    ) -> Result<()> {
+        process_withdraw_vote(ctx, proposal_id)
+    }
+    
+    // Once voting ends, anyone can count the votes and mark the proposal passed or rejected
+    pub fn finalize_proposal(
+        ctx: Context<FinalizeProposal>,
+        proposal_id: u64
+    ) -> Result<()> {
+        process_finalize_proposal(ctx, proposal_id)
+    }
+    
     // Once a proposal passes, it can be executed to update the protocol
     pub fn execute_proposal(
         ctx: Context<ExecuteProposal>,
@@ -82,5 +237,21 @@ pub mod stablecoin {
     ) -> Result<()> {
         process_execute_proposal(ctx, proposal_id)
     }
+    
+    // Proposers can cancel their own proposals while voting is open, and the guardian can veto until execution
+    pub fn cancel_proposal(
+        ctx: Context<CancelProposal>,
+        proposal_id: u64
+    ) -> Result<()> {
+        process_cancel_proposal(ctx, proposal_id)
+    }
+    
+    // Proposers can reclaim the rent of a finished proposal
+    pub fn close_proposal(
+        ctx: Context<CloseProposal>,
+        proposal_id: u64
+    ) -> Result<()> {
+        process_close_proposal(ctx, proposal_id)
+    }
 }
 
@@ -0,0 +1,306 @@
+use std::fmt;
+
+use anchor_lang::prelude::*;
+
+use crate::{CustomError, BPS_DIVISOR};
+
+// I keep 18 decimals of precision for every risk calculation
+pub const WAD_DECIMALS: u32 = 18;
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+// A non-negative fixed-point number scaled by WAD.
+// Every operation is checked, and I always pick the rounding direction explicitly:
+// anything that counts in the user's favour (collateral) rounds down,
+// anything that counts against them (debt, fees) rounds up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(u128);
+
+impl Decimal {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(WAD);
+    pub const MAX: Self = Self(u128::MAX);
+
+    pub fn from_raw(raw: u128) -> Self {
+        Self(raw)
+    }
+
+    pub fn raw(self) -> u128 {
+        self.0
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        // A u64 times 10^18 always fits in a u128
+        Self(value as u128 * WAD)
+    }
+
+    pub fn from_percent(percent: u64) -> Self {
+        Self(percent as u128 * WAD / 100)
+    }
+
+    pub fn from_bps(bps: u64) -> Self {
+        Self(bps as u128 * WAD / BPS_DIVISOR as u128)
+    }
+
+    // I build value = numerator / denominator, rounding down
+    pub fn from_ratio(numerator: u64, denominator: u64) -> Result<Self> {
+        Self::from_u64(numerator).try_div(Self::from_u64(denominator))
+    }
+
+    pub fn try_add(self, rhs: Self) -> Result<Self> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Self)
+            .ok_or(error!(CustomError::MathOverflow))
+    }
+
+    pub fn try_sub(self, rhs: Self) -> Result<Self> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(Self)
+            .ok_or(error!(CustomError::MathOverflow))
+    }
+
+    // Multiplication rounding down - for values that favour the user
+    pub fn try_mul(self, rhs: Self) -> Result<Self> {
+        mul_div_floor_u128(self.0, rhs.0, WAD).map(Self)
+    }
+
+    // Multiplication rounding up - for values that count against the user
+    pub fn try_mul_ceil(self, rhs: Self) -> Result<Self> {
+        mul_div_ceil_u128(self.0, rhs.0, WAD).map(Self)
+    }
+
+    // Division rounding down - for values that favour the user
+    pub fn try_div(self, rhs: Self) -> Result<Self> {
+        require!(rhs.0 != 0, CustomError::MathOverflow);
+        mul_div_floor_u128(self.0, WAD, rhs.0).map(Self)
+    }
+
+    // Division rounding up - for values that count against the user
+    pub fn try_div_ceil(self, rhs: Self) -> Result<Self> {
+        require!(rhs.0 != 0, CustomError::MathOverflow);
+        mul_div_ceil_u128(self.0, WAD, rhs.0).map(Self)
+    }
+
+    pub fn try_floor_u64(self) -> Result<u64> {
+        u64::try_from(self.0 / WAD).map_err(|_| error!(CustomError::MathOverflow))
+    }
+
+    pub fn try_ceil_u64(self) -> Result<u64> {
+        u64::try_from(self.0.div_ceil(WAD)).map_err(|_| error!(CustomError::MathOverflow))
+    }
+
+    // I express the value in basis points, rounding down
+    pub fn try_floor_bps(self) -> Result<u64> {
+        let bps = mul_div_floor_u128(self.0, BPS_DIVISOR as u128, WAD)?;
+        u64::try_from(bps).map_err(|_| error!(CustomError::MathOverflow))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Self::MAX {
+            return write!(f, "MAX");
+        }
+        // I print four decimal places, which is plenty for logs
+        let integer = self.0 / WAD;
+        let fraction = (self.0 % WAD) / 10u128.pow(WAD_DECIMALS - 4);
+        write!(f, "{}.{:04}", integer, fraction)
+    }
+}
+
+// value * numerator / denominator, rounding down
+pub fn mul_div_floor(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    let result = mul_div_floor_u128(value as u128, numerator as u128, denominator as u128)?;
+    u64::try_from(result).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+// value * numerator / denominator, rounding up
+pub fn mul_div_ceil(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    let result = mul_div_ceil_u128(value as u128, numerator as u128, denominator as u128)?;
+    u64::try_from(result).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+fn mul_div_floor_u128(value: u128, numerator: u128, denominator: u128) -> Result<u128> {
+    mul_div_u128(value, numerator, denominator, false)
+}
+
+fn mul_div_ceil_u128(value: u128, numerator: u128, denominator: u128) -> Result<u128> {
+    mul_div_u128(value, numerator, denominator, true)
+}
+
+fn mul_div_u128(value: u128, numerator: u128, denominator: u128, round_up: bool) -> Result<u128> {
+    require!(denominator != 0, CustomError::MathOverflow);
+
+    // I keep the full 256-bit product so WAD * WAD never overflows in the middle
+    let (high, low) = widening_mul(value, numerator);
+    let (quotient, remainder) = if high == 0 {
+        (low / denominator, low % denominator)
+    } else {
+        wide_div(high, low, denominator).ok_or(error!(CustomError::MathOverflow))?
+    };
+
+    if round_up && remainder != 0 {
+        quotient.checked_add(1).ok_or(error!(CustomError::MathOverflow))
+    } else {
+        Ok(quotient)
+    }
+}
+
+// Multiplies two u128 values into a (high, low) 256-bit pair
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    let mask = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & mask);
+    let (b_high, b_low) = (b >> 64, b & mask);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (low_high & mask) + (high_low & mask);
+    let low = (low_low & mask) | (middle << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+// Divides a 256-bit (high, low) value by a u128, returning None if the quotient won't fit
+fn wide_div(high: u128, low: u128, divisor: u128) -> Option<(u128, u128)> {
+    if high >= divisor {
+        return None;
+    }
+
+    // Plain shift-and-subtract long division, one bit of the low word at a time
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= divisor {
+            remainder = remainder.wrapping_sub(divisor);
+            quotient |= 1;
+        }
+    }
+    Some((quotient, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overflow() -> Option<Error> {
+        Some(CustomError::MathOverflow.into())
+    }
+
+    #[test]
+    fn mul_div_rounds_in_the_direction_asked() {
+        assert_eq!(mul_div_floor(10, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div_ceil(10, 1, 3).unwrap(), 4);
+        assert_eq!(mul_div_floor(9, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div_ceil(9, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div_floor(0, 7, 3).unwrap(), 0);
+        assert_eq!(mul_div_ceil(0, 7, 3).unwrap(), 0);
+    }
+
+    #[test]
+    fn decimal_ops_round_in_the_direction_asked() {
+        let third = Decimal::from_ratio(1, 3).unwrap();
+        assert_eq!(third.raw(), 333_333_333_333_333_333);
+        assert_eq!(Decimal::ONE.try_div_ceil(Decimal::from_u64(3)).unwrap().raw(), 333_333_333_333_333_334);
+
+        // 0.333...333 * 0.333...333 lands between two representable values
+        let product = third.try_mul(third).unwrap();
+        assert_eq!(product.raw(), 111_111_111_111_111_110);
+        assert_eq!(third.try_mul_ceil(third).unwrap().raw(), 111_111_111_111_111_111);
+
+        let two_and_a_third = Decimal::from_u64(2).try_add(third).unwrap();
+        assert_eq!(two_and_a_third.try_floor_u64().unwrap(), 2);
+        assert_eq!(two_and_a_third.try_ceil_u64().unwrap(), 3);
+        assert_eq!(two_and_a_third.try_floor_bps().unwrap(), 23_333);
+
+        // Exact results don't move in either direction
+        let half = Decimal::from_bps(5_000);
+        assert_eq!(half.try_mul(Decimal::from_u64(4)).unwrap(), Decimal::from_u64(2));
+        assert_eq!(half.try_mul_ceil(Decimal::from_u64(4)).unwrap(), Decimal::from_u64(2));
+        assert_eq!(Decimal::from_u64(2).try_floor_u64().unwrap(), 2);
+        assert_eq!(Decimal::from_u64(2).try_ceil_u64().unwrap(), 2);
+    }
+
+    #[test]
+    fn widening_mul_keeps_the_high_word() {
+        assert_eq!(widening_mul(1 << 127, 4), (2, 0));
+        assert_eq!(widening_mul(u64::MAX as u128, u64::MAX as u128), (0, (u64::MAX as u128) * (u64::MAX as u128)));
+        // (2^128 - 1)^2 = 2^256 - 2^129 + 1
+        assert_eq!(widening_mul(u128::MAX, u128::MAX), (u128::MAX - 1, 1));
+    }
+
+    #[test]
+    fn products_beyond_u128_take_the_wide_path() {
+        // 2^128 - 1 times itself only fits in 256 bits
+        assert_eq!(mul_div_floor_u128(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+        assert_eq!(mul_div_ceil_u128(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+
+        // (2^128 - 1) * 3 / 2 doesn't fit, but (2^128 - 1) * 3 / 4 does
+        let value = mul_div_floor_u128(u128::MAX, 3, 4).unwrap();
+        assert_eq!(value, u128::MAX / 4 * 3 + 2);
+        assert_eq!(mul_div_ceil_u128(u128::MAX, 3, 4).unwrap(), value + 1);
+
+        // A large WAD value times a large WAD value overflows u128 before the division
+        let big = Decimal::from_u64(u64::MAX);
+        let product = big.try_mul(Decimal::from_u64(2)).unwrap();
+        assert_eq!(product.raw(), u64::MAX as u128 * 2 * WAD);
+        assert_eq!(product.try_div(Decimal::from_u64(2)).unwrap(), big);
+
+        let third_of_big = big.try_div(Decimal::from_u64(3)).unwrap();
+        assert_eq!(third_of_big.raw(), u64::MAX as u128 * WAD / 3);
+        assert_eq!(big.try_div_ceil(Decimal::from_u64(3)).unwrap().raw(), (u64::MAX as u128 * WAD).div_ceil(3));
+    }
+
+    #[test]
+    fn rejects_division_by_zero() {
+        assert_eq!(mul_div_floor(1, 1, 0).err(), overflow());
+        assert_eq!(mul_div_ceil(1, 1, 0).err(), overflow());
+        assert_eq!(Decimal::ONE.try_div(Decimal::ZERO).err(), overflow());
+        assert_eq!(Decimal::ONE.try_div_ceil(Decimal::ZERO).err(), overflow());
+        assert_eq!(Decimal::from_ratio(1, 0).err(), overflow());
+    }
+
+    #[test]
+    fn rejects_results_that_dont_fit() {
+        // The quotient needs more than 128 bits
+        assert_eq!(mul_div_floor_u128(u128::MAX, u128::MAX, 1).err(), overflow());
+        assert_eq!(mul_div_floor_u128(u128::MAX, 2, 1).err(), overflow());
+        assert_eq!(Decimal::MAX.try_mul(Decimal::from_u64(2)).err(), overflow());
+        assert_eq!(Decimal::MAX.try_div(Decimal::from_bps(5_000)).err(), overflow());
+
+        // 7 * 97223533405982418132392744980505203273 = 2 * (2^128 - 1) + 1, so the floor is the largest
+        // u128 and rounding it up has nowhere to go
+        let factor = 97_223_533_405_982_418_132_392_744_980_505_203_273;
+        assert_eq!(mul_div_floor_u128(7, factor, 2).unwrap(), u128::MAX);
+        assert_eq!(mul_div_ceil_u128(7, factor, 2).err(), overflow());
+
+        // The quotient fits in u128 but not in u64
+        assert_eq!(mul_div_floor(u64::MAX, 2, 1).err(), overflow());
+        assert_eq!(mul_div_ceil(u64::MAX, 3, 2).err(), overflow());
+        assert_eq!(Decimal::MAX.try_floor_u64().err(), overflow());
+        assert_eq!(Decimal::MAX.try_ceil_u64().err(), overflow());
+
+        assert_eq!(Decimal::MAX.try_add(Decimal::from_raw(1)).err(), overflow());
+        assert_eq!(Decimal::ZERO.try_sub(Decimal::from_raw(1)).err(), overflow());
+    }
+
+    #[test]
+    fn health_factor_keeps_its_fraction() {
+        // $199 of threshold-adjusted collateral against $100 of debt used to come out as a health factor of 1
+        let health_factor = Decimal::from_u64(199).try_div(Decimal::from_u64(100)).unwrap();
+        assert_eq!(health_factor.try_floor_bps().unwrap(), 19_900);
+        assert_eq!(health_factor.to_string(), "1.9900");
+        assert!(health_factor > Decimal::ONE);
+        assert!(health_factor < Decimal::from_bps(20_000));
+
+        // One cent short of 2.0 rounds down rather than up to the threshold
+        let just_short = Decimal::from_u64(199_999_999).try_div(Decimal::from_u64(100_000_000)).unwrap();
+        assert!(just_short < Decimal::from_bps(20_000));
+    }
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::{oracle::{OraclePrice, PriceSource}, CustomError};
+
+// A governance-set price for pegged collateral that doesn't need a live feed
+pub struct FixedPriceSource {
+    price: i64,
+    exponent: i32,
+}
+
+impl FixedPriceSource {
+    pub fn new(price: i64, exponent: i32) -> Result<Self> {
+        // I refuse to run with a fixed price that was never set
+        require!(price > 0, CustomError::InvalidPrice);
+        Ok(Self { price, exponent })
+    }
+}
+
+impl PriceSource for FixedPriceSource {
+    fn get_price(&self, clock: &Clock, _maximum_age: u64) -> Result<OraclePrice> {
+        // A fixed price is never stale and carries no uncertainty
+        Ok(OraclePrice {
+            price: self.price,
+            conf: 0,
+            exponent: self.exponent,
+            publish_time: clock.unix_timestamp,
+            ema_price: self.price,
+            ema_conf: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_fixed_price_as_fresh_and_certain() {
+        let clock = Clock { unix_timestamp: 1_700_000_000, ..Clock::default() };
+        let price = FixedPriceSource::new(100_000_000, -8).unwrap().get_price(&clock, 0).unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price: 100_000_000,
+                conf: 0,
+                exponent: -8,
+                publish_time: 1_700_000_000,
+                ema_price: 100_000_000,
+                ema_conf: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_an_unset_price() {
+        assert_eq!(FixedPriceSource::new(0, -8).err(), Some(CustomError::InvalidPrice.into()));
+        assert_eq!(FixedPriceSource::new(-1, -8).err(), Some(CustomError::InvalidPrice.into()));
+    }
+}
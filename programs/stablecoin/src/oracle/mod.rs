@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+
+use crate::{CustomError, OracleConfig, OracleSource};
+
+pub use fixed::*;
+pub use pyth::*;
+pub use stake_pool::*;
+pub use switchboard::*;
+
+pub mod fixed;
+pub mod pyth;
+pub mod stake_pool;
+pub mod switchboard;
+
+// Every adapter hands me the same normalized shape, so the rest of the program
+// never needs to know which oracle network the price came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    // The raw price, scaled by 10^exponent
+    pub price: i64,
+
+    // The confidence interval around the price, in the same scale
+    pub conf: u64,
+
+    // The power of ten the price and confidence are expressed in
+    pub exponent: i32,
+
+    // When the oracle published this price (unix seconds)
+    pub publish_time: i64,
+
+    // The exponentially-weighted moving average price and its confidence, in the same scale.
+    // Sources without an EMA report their spot values here.
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+pub trait PriceSource {
+    // I return the latest price, failing if it's older than maximum_age seconds
+    fn get_price(&self, clock: &Clock, maximum_age: u64) -> Result<OraclePrice>;
+
+    // Some sources carry a second feed I can cross-check against
+    fn get_backup_price(&self, _clock: &Clock, _maximum_age: u64) -> Option<Result<OraclePrice>> {
+        None
+    }
+}
+
+pub fn load_price_source(
+    oracle_config: &OracleConfig,
+    price_account: &AccountInfo,
+) -> Result<Box<dyn PriceSource>> {
+    // I pick the adapter the oracle config tells me to use
+    match oracle_config.source {
+        OracleSource::Pyth => {
+            // Anyone can post a PriceUpdateV2 account, so I only accept the one I've pinned
+            require_keys_eq!(
+                price_account.key(),
+                oracle_config.pyth_price_account,
+                CustomError::InvalidPriceAccount
+            );
+            Ok(Box::new(PythPriceSource::from_account_info(price_account)?))
+        }
+        OracleSource::Switchboard => {
+            // A Switchboard feed is identified by its account, so I only accept the configured one
+            require_keys_eq!(
+                price_account.key(),
+                oracle_config.switchboard_feed,
+                CustomError::InvalidPriceAccount
+            );
+            Ok(Box::new(SwitchboardPriceSource::from_account_info(price_account)?))
+        }
+        OracleSource::Fixed => Ok(Box::new(FixedPriceSource::new(
+            oracle_config.fixed_price,
+            oracle_config.fixed_exponent,
+        )?)),
+    }
+}
@@ -0,0 +1,213 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::{
+    error::GetPriceError,
+    price_update::{get_feed_id_from_hex, FeedId, PriceUpdateV2, VerificationLevel},
+};
+
+use crate::{oracle::{OraclePrice, PriceSource}, CustomError, BACKUP_FEED_ID, FEED_ID};
+
+// Reads prices out of a Pyth pull-oracle PriceUpdateV2 account
+pub struct PythPriceSource {
+    update: PriceUpdateV2,
+    feed_id: FeedId,
+    backup_feed_id: Option<FeedId>,
+}
+
+impl PythPriceSource {
+    pub fn new(update: PriceUpdateV2, feed_id: FeedId, backup_feed_id: Option<FeedId>) -> Self {
+        Self { update, feed_id, backup_feed_id }
+    }
+
+    pub fn from_account_info(price_account: &AccountInfo) -> Result<Self> {
+        // I only trust updates written by the Pyth receiver program
+        require_keys_eq!(
+            *price_account.owner,
+            pyth_solana_receiver_sdk::ID,
+            CustomError::InvalidPriceAccount
+        );
+        let data = price_account.try_borrow_data()?;
+        Self::from_account_data(&data)
+    }
+
+    // Collateral tokens with their own feed are read the same way, just without a backup feed
+    pub fn from_account_info_for_feed(price_account: &AccountInfo, feed_id: FeedId) -> Result<Self> {
+        let mut source = Self::from_account_info(price_account)?;
+        source.feed_id = feed_id;
+        source.backup_feed_id = None;
+        Ok(source)
+    }
+
+    // The unit tests below hand me raw fixture bytes instead of a live account
+    pub fn from_account_data(mut data: &[u8]) -> Result<Self> {
+        // This checks the discriminator before decoding the update
+        let update = PriceUpdateV2::try_deserialize(&mut data)?;
+
+        // Partially verified updates carry fewer guardian signatures than I'm willing to trust
+        require!(
+            matches!(update.verification_level, VerificationLevel::Full),
+            CustomError::UnverifiedPriceUpdate
+        );
+        // A feed id that doesn't parse is a bug, not a missing backup, so I fail rather than skip the cross-check
+        Ok(Self::new(
+            update,
+            get_feed_id_from_hex(FEED_ID)?,
+            Some(get_feed_id_from_hex(BACKUP_FEED_ID)?),
+        ))
+    }
+
+    fn read(&self, clock: &Clock, maximum_age: u64, feed_id: &FeedId) -> Result<OraclePrice> {
+        let price = self
+            .update
+            .get_price_no_older_than_with_custom_verification_level(
+                clock,
+                maximum_age,
+                feed_id,
+                VerificationLevel::Full,
+            )
+            .map_err(|err| match err {
+                // I report staleness the same way for every source so fallback can kick in
+                GetPriceError::PriceTooOld => error!(CustomError::StaleOracleData),
+                err => err.into(),
+            })?;
+        Ok(OraclePrice {
+            price: price.price,
+            conf: price.conf,
+            exponent: price.exponent,
+            publish_time: price.publish_time,
+            // The EMA shares the message (and exponent) of the price I just validated
+            ema_price: self.update.price_message.ema_price,
+            ema_conf: self.update.price_message.ema_conf,
+        })
+    }
+}
+
+impl PriceSource for PythPriceSource {
+    fn get_price(&self, clock: &Clock, maximum_age: u64) -> Result<OraclePrice> {
+        self.read(clock, maximum_age, &self.feed_id)
+    }
+
+    fn get_backup_price(&self, clock: &Clock, maximum_age: u64) -> Option<Result<OraclePrice>> {
+        self.backup_feed_id
+            .as_ref()
+            .map(|feed_id| self.read(clock, maximum_age, feed_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAXIMUM_AGE;
+    use pyth_solana_receiver_sdk::price_update::PriceFeedMessage;
+
+    const PUBLISH_TIME: i64 = 1_700_000_000;
+
+    fn fixture(verification_level: VerificationLevel, feed_id: FeedId) -> Vec<u8> {
+        let update = PriceUpdateV2 {
+            write_authority: Pubkey::new_unique(),
+            verification_level,
+            price_message: PriceFeedMessage {
+                feed_id,
+                price: 15_012_345_678,
+                conf: 7_500_000,
+                exponent: -8,
+                publish_time: PUBLISH_TIME,
+                prev_publish_time: PUBLISH_TIME - 1,
+                ema_price: 14_990_000_000,
+                ema_conf: 8_000_000,
+            },
+            posted_slot: 1_000,
+        };
+        let mut data = Vec::new();
+        update.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn sol_usd() -> FeedId {
+        get_feed_id_from_hex(FEED_ID).unwrap()
+    }
+
+    fn clock_at(unix_timestamp: i64) -> Clock {
+        Clock { unix_timestamp, ..Clock::default() }
+    }
+
+    #[test]
+    fn reads_a_fully_verified_update() {
+        let source = PythPriceSource::from_account_data(&fixture(VerificationLevel::Full, sol_usd())).unwrap();
+        let price = source.get_price(&clock_at(PUBLISH_TIME + 10), MAXIMUM_AGE).unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price: 15_012_345_678,
+                conf: 7_500_000,
+                exponent: -8,
+                publish_time: PUBLISH_TIME,
+                ema_price: 14_990_000_000,
+                ema_conf: 8_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_partially_verified_updates() {
+        let data = fixture(VerificationLevel::Partial { num_signatures: 12 }, sol_usd());
+        assert_eq!(
+            PythPriceSource::from_account_data(&data).err(),
+            Some(CustomError::UnverifiedPriceUpdate.into())
+        );
+    }
+
+    #[test]
+    fn rejects_other_account_types() {
+        let mut data = fixture(VerificationLevel::Full, sol_usd());
+        data[0] ^= 0xff;
+        assert!(PythPriceSource::from_account_data(&data).is_err());
+        assert!(PythPriceSource::from_account_data(&data[..8]).is_err());
+    }
+
+    #[test]
+    fn rejects_a_different_feed() {
+        let source = PythPriceSource::from_account_data(&fixture(VerificationLevel::Full, [7; 32])).unwrap();
+        assert_eq!(
+            source.get_price(&clock_at(PUBLISH_TIME), MAXIMUM_AGE).err(),
+            Some(GetPriceError::MismatchedFeedId.into())
+        );
+    }
+
+    #[test]
+    fn reports_old_prices_as_stale() {
+        let source = PythPriceSource::from_account_data(&fixture(VerificationLevel::Full, sol_usd())).unwrap();
+        let at_limit = clock_at(PUBLISH_TIME + MAXIMUM_AGE as i64);
+        assert!(source.get_price(&at_limit, MAXIMUM_AGE).is_ok());
+
+        let too_late = clock_at(PUBLISH_TIME + MAXIMUM_AGE as i64 + 1);
+        assert_eq!(
+            source.get_price(&too_late, MAXIMUM_AGE).err(),
+            Some(CustomError::StaleOracleData.into())
+        );
+    }
+
+    #[test]
+    fn parses_both_sol_usd_feed_ids() {
+        let source = PythPriceSource::from_account_data(&fixture(VerificationLevel::Full, sol_usd())).unwrap();
+        assert_eq!(source.backup_feed_id, Some(get_feed_id_from_hex(BACKUP_FEED_ID).unwrap()));
+        assert_ne!(source.backup_feed_id, Some(sol_usd()));
+
+        // The backup feed isn't in this update, which the cross-check hears about as an error
+        assert_eq!(
+            source.get_backup_price(&clock_at(PUBLISH_TIME), MAXIMUM_AGE).map(|price| price.err()),
+            Some(Some(GetPriceError::MismatchedFeedId.into()))
+        );
+    }
+
+    #[test]
+    fn reads_a_collateral_feed_without_a_backup() {
+        let feed_id = [3; 32];
+        let source = PythPriceSource::new(
+            PriceUpdateV2::try_deserialize(&mut &fixture(VerificationLevel::Full, feed_id)[..]).unwrap(),
+            feed_id,
+            None,
+        );
+        assert!(source.get_price(&clock_at(PUBLISH_TIME), MAXIMUM_AGE).is_ok());
+        assert!(source.get_backup_price(&clock_at(PUBLISH_TIME), MAXIMUM_AGE).is_none());
+    }
+}
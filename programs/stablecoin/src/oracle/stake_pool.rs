@@ -0,0 +1,194 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    mul_div_floor, CustomError, BPS_DIVISOR, MAX_LST_EXCHANGE_RATE_BPS, MIN_LST_EXCHANGE_RATE_BPS,
+    SPL_STAKE_POOL_PROGRAM_ID,
+};
+
+// Byte offsets into an SPL stake pool account. I only read the fields I need rather
+// than pulling in the whole stake pool crate.
+const ACCOUNT_TYPE_OFFSET: usize = 0;
+const POOL_MINT_OFFSET: usize = 162;
+const TOTAL_LAMPORTS_OFFSET: usize = 258;
+const POOL_TOKEN_SUPPLY_OFFSET: usize = 266;
+const LAST_UPDATE_EPOCH_OFFSET: usize = 274;
+const STAKE_POOL_MIN_LEN: usize = LAST_UPDATE_EPOCH_OFFSET + 8;
+
+// The stake pool program tags initialized pools with this account type
+const ACCOUNT_TYPE_STAKE_POOL: u8 = 1;
+
+// How many lamports of staked SOL back each pool token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StakePoolExchangeRate {
+    pub pool_mint: Pubkey,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+    pub last_update_epoch: u64,
+}
+
+impl StakePoolExchangeRate {
+    pub fn from_account_info(stake_pool: &AccountInfo) -> Result<Self> {
+        // I only trust pools run by the SPL stake pool program
+        require_keys_eq!(
+            *stake_pool.owner,
+            SPL_STAKE_POOL_PROGRAM_ID,
+            CustomError::InvalidStakePool
+        );
+        let data = stake_pool.try_borrow_data()?;
+        Self::from_account_data(&data, Clock::get()?.epoch)
+    }
+
+    // The unit tests below hand me locally built stake pool fixtures instead of a live account
+    pub fn from_account_data(data: &[u8], current_epoch: u64) -> Result<Self> {
+        require!(
+            data.len() >= STAKE_POOL_MIN_LEN && data[ACCOUNT_TYPE_OFFSET] == ACCOUNT_TYPE_STAKE_POOL,
+            CustomError::InvalidStakePool
+        );
+
+        let rate = Self {
+            pool_mint: Pubkey::new_from_array(read_array(data, POOL_MINT_OFFSET)),
+            total_lamports: u64::from_le_bytes(read_array(data, TOTAL_LAMPORTS_OFFSET)),
+            pool_token_supply: u64::from_le_bytes(read_array(data, POOL_TOKEN_SUPPLY_OFFSET)),
+            last_update_epoch: u64::from_le_bytes(read_array(data, LAST_UPDATE_EPOCH_OFFSET)),
+        };
+
+        // Rewards only land in total_lamports when the pool is updated, so a pool that
+        // hasn't been cranked this epoch is pricing its token at a stale rate
+        require!(rate.last_update_epoch == current_epoch, CustomError::StakePoolNotUpdated);
+        rate.check_bounds()?;
+        Ok(rate)
+    }
+
+    fn check_bounds(&self) -> Result<()> {
+        // An empty pool has no meaningful rate
+        require!(self.pool_token_supply > 0, CustomError::ExchangeRateOutOfBounds);
+
+        let rate_bps = mul_div_floor(self.total_lamports, BPS_DIVISOR as u64, self.pool_token_supply)?;
+        require!(
+            (MIN_LST_EXCHANGE_RATE_BPS..=MAX_LST_EXCHANGE_RATE_BPS).contains(&rate_bps),
+            CustomError::ExchangeRateOutOfBounds
+        );
+        Ok(())
+    }
+
+    // The SOL backing an amount of pool tokens, rounding down
+    pub fn lamports_for(&self, pool_tokens: u64) -> Result<u64> {
+        mul_div_floor(pool_tokens, self.total_lamports, self.pool_token_supply)
+    }
+
+    // The pool tokens worth an amount of SOL, rounding down
+    pub fn pool_tokens_for(&self, lamports: u64) -> Result<u64> {
+        mul_div_floor(lamports, self.pool_token_supply, self.total_lamports)
+    }
+}
+
+fn read_array<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    bytes.copy_from_slice(&data[offset..offset + N]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPOCH: u64 = 600;
+
+    fn fixture(total_lamports: u64, pool_token_supply: u64, last_update_epoch: u64) -> (Pubkey, Vec<u8>) {
+        let pool_mint = Pubkey::new_unique();
+        let mut data = vec![0u8; STAKE_POOL_MIN_LEN + 100];
+        data[ACCOUNT_TYPE_OFFSET] = ACCOUNT_TYPE_STAKE_POOL;
+        data[POOL_MINT_OFFSET..POOL_MINT_OFFSET + 32].copy_from_slice(pool_mint.as_ref());
+        data[TOTAL_LAMPORTS_OFFSET..TOTAL_LAMPORTS_OFFSET + 8].copy_from_slice(&total_lamports.to_le_bytes());
+        data[POOL_TOKEN_SUPPLY_OFFSET..POOL_TOKEN_SUPPLY_OFFSET + 8].copy_from_slice(&pool_token_supply.to_le_bytes());
+        data[LAST_UPDATE_EPOCH_OFFSET..LAST_UPDATE_EPOCH_OFFSET + 8].copy_from_slice(&last_update_epoch.to_le_bytes());
+        (pool_mint, data)
+    }
+
+    fn out_of_bounds() -> Option<Error> {
+        Some(CustomError::ExchangeRateOutOfBounds.into())
+    }
+
+    #[test]
+    fn reads_a_valid_pool() {
+        let (pool_mint, data) = fixture(1_150_000_000_000, 1_000_000_000_000, EPOCH);
+        assert_eq!(
+            StakePoolExchangeRate::from_account_data(&data, EPOCH).unwrap(),
+            StakePoolExchangeRate {
+                pool_mint,
+                total_lamports: 1_150_000_000_000,
+                pool_token_supply: 1_000_000_000_000,
+                last_update_epoch: EPOCH,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_a_short_buffer() {
+        let (_, data) = fixture(1_150, 1_000, EPOCH);
+        assert_eq!(
+            StakePoolExchangeRate::from_account_data(&data[..STAKE_POOL_MIN_LEN - 1], EPOCH).err(),
+            Some(CustomError::InvalidStakePool.into())
+        );
+    }
+
+    #[test]
+    fn rejects_other_account_types() {
+        let (_, mut data) = fixture(1_150, 1_000, EPOCH);
+        // 0 is an uninitialized account and 2 a validator list
+        for account_type in [0, 2] {
+            data[ACCOUNT_TYPE_OFFSET] = account_type;
+            assert_eq!(
+                StakePoolExchangeRate::from_account_data(&data, EPOCH).err(),
+                Some(CustomError::InvalidStakePool.into())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_pool_not_updated_this_epoch() {
+        let (_, data) = fixture(1_150, 1_000, EPOCH - 1);
+        assert_eq!(
+            StakePoolExchangeRate::from_account_data(&data, EPOCH).err(),
+            Some(CustomError::StakePoolNotUpdated.into())
+        );
+    }
+
+    #[test]
+    fn rejects_an_empty_pool() {
+        let (_, data) = fixture(0, 0, EPOCH);
+        assert_eq!(StakePoolExchangeRate::from_account_data(&data, EPOCH).err(), out_of_bounds());
+    }
+
+    #[test]
+    fn rejects_rates_outside_one_to_two_sol() {
+        let (_, below_one) = fixture(999_999, 1_000_000, EPOCH);
+        assert_eq!(StakePoolExchangeRate::from_account_data(&below_one, EPOCH).err(), out_of_bounds());
+
+        let (_, above_two) = fixture(2_000_100, 1_000_000, EPOCH);
+        assert_eq!(StakePoolExchangeRate::from_account_data(&above_two, EPOCH).err(), out_of_bounds());
+
+        let (_, exactly_one) = fixture(1_000_000, 1_000_000, EPOCH);
+        assert!(StakePoolExchangeRate::from_account_data(&exactly_one, EPOCH).is_ok());
+
+        let (_, exactly_two) = fixture(2_000_000, 1_000_000, EPOCH);
+        assert!(StakePoolExchangeRate::from_account_data(&exactly_two, EPOCH).is_ok());
+    }
+
+    #[test]
+    fn conversions_round_down() {
+        // 1.5 lamports per pool token
+        let (_, data) = fixture(3, 2, EPOCH);
+        let rate = StakePoolExchangeRate::from_account_data(&data, EPOCH).unwrap();
+
+        assert_eq!(rate.lamports_for(3).unwrap(), 4);
+        assert_eq!(rate.lamports_for(4).unwrap(), 6);
+        assert_eq!(rate.pool_tokens_for(4).unwrap(), 2);
+        assert_eq!(rate.pool_tokens_for(6).unwrap(), 4);
+
+        // Round-tripping never hands back more than I started with
+        for pool_tokens in 0..50 {
+            assert!(rate.pool_tokens_for(rate.lamports_for(pool_tokens).unwrap()).unwrap() <= pool_tokens);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+use switchboard_on_demand::{sb_pid, Discriminator, PullFeedAccountData, PRECISION};
+
+use crate::{oracle::{OraclePrice, PriceSource}, CustomError, SWITCHBOARD_PRICE_EXPONENT};
+
+// Reads prices out of a Switchboard On-Demand pull feed account
+pub struct SwitchboardPriceSource {
+    value: i128,
+    std_dev: i128,
+    result_slot: u64,
+    max_staleness: u32,
+    last_update_timestamp: i64,
+}
+
+impl SwitchboardPriceSource {
+    pub fn from_account_info(price_account: &AccountInfo) -> Result<Self> {
+        // I only trust feeds owned by the Switchboard program
+        require_keys_eq!(*price_account.owner, sb_pid(), CustomError::InvalidPriceAccount);
+        let data = price_account.try_borrow_data()?;
+        Self::from_account_data(&data)
+    }
+
+    // The unit tests below hand me raw fixture bytes instead of a live account
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let discriminator = PullFeedAccountData::DISCRIMINATOR;
+        let end = discriminator.len() + std::mem::size_of::<PullFeedAccountData>();
+        require!(
+            data.len() >= end && data[..discriminator.len()] == discriminator,
+            CustomError::InvalidPriceAccount
+        );
+        // The account data isn't guaranteed to be aligned, so I copy the feed out
+        let feed: PullFeedAccountData = bytemuck::pod_read_unaligned(&data[discriminator.len()..end]);
+
+        Ok(Self {
+            value: feed.result.value,
+            std_dev: feed.result.std_dev,
+            result_slot: feed.result.slot,
+            max_staleness: feed.max_staleness,
+            last_update_timestamp: feed.last_update_timestamp,
+        })
+    }
+
+    fn rescale(value: i128) -> i128 {
+        // Switchboard reports with 18 decimals; I bring that down to my exponent
+        let shift = PRECISION as i32 + SWITCHBOARD_PRICE_EXPONENT;
+        value / 10i128.pow(shift as u32)
+    }
+}
+
+impl PriceSource for SwitchboardPriceSource {
+    fn get_price(&self, clock: &Clock, maximum_age: u64) -> Result<OraclePrice> {
+        // I need a result that landed within the feed's own slot window...
+        require!(
+            self.result_slot != 0
+                && self.result_slot >= clock.slot.saturating_sub(self.max_staleness as u64),
+            CustomError::StaleOracleData
+        );
+        // ...and that isn't older than my own maximum age
+        require!(
+            self.last_update_timestamp.saturating_add(maximum_age as i64) >= clock.unix_timestamp,
+            CustomError::StaleOracleData
+        );
+
+        let price = i64::try_from(Self::rescale(self.value))
+            .map_err(|_| error!(CustomError::InvalidPrice))?;
+        let conf = u64::try_from(Self::rescale(self.std_dev))
+            .map_err(|_| error!(CustomError::InvalidPrice))?;
+
+        Ok(OraclePrice {
+            price,
+            conf,
+            exponent: SWITCHBOARD_PRICE_EXPONENT,
+            publish_time: self.last_update_timestamp,
+            // Switchboard has no EMA, so I fall back to the spot result
+            ema_price: price,
+            ema_conf: conf,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAXIMUM_AGE;
+
+    const UPDATED_AT: i64 = 1_700_000_000;
+    const RESULT_SLOT: u64 = 5_000;
+    const MAX_STALENESS: u32 = 150;
+
+    fn fixture(value: i128, std_dev: i128) -> Vec<u8> {
+        let mut feed: PullFeedAccountData = bytemuck::Zeroable::zeroed();
+        feed.result.value = value;
+        feed.result.std_dev = std_dev;
+        feed.result.slot = RESULT_SLOT;
+        feed.max_staleness = MAX_STALENESS;
+        feed.last_update_timestamp = UPDATED_AT;
+
+        let mut data = PullFeedAccountData::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(&feed));
+        data
+    }
+
+    fn clock_at(slot: u64, unix_timestamp: i64) -> Clock {
+        Clock { slot, unix_timestamp, ..Clock::default() }
+    }
+
+    #[test]
+    fn rescales_eighteen_decimals_to_eight() {
+        // 150.123456789123456789 with a 0.05 standard deviation
+        let source = SwitchboardPriceSource::from_account_data(
+            &fixture(150_123_456_789_123_456_789, 50_000_000_000_000_000),
+        )
+        .unwrap();
+        let price = source.get_price(&clock_at(RESULT_SLOT, UPDATED_AT), MAXIMUM_AGE).unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price: 15_012_345_678,
+                conf: 5_000_000,
+                exponent: -8,
+                publish_time: UPDATED_AT,
+                ema_price: 15_012_345_678,
+                ema_conf: 5_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_results_outside_the_feed_slot_window() {
+        let source = SwitchboardPriceSource::from_account_data(&fixture(10i128.pow(18), 0)).unwrap();
+        let last_fresh_slot = RESULT_SLOT + MAX_STALENESS as u64;
+        assert!(source.get_price(&clock_at(last_fresh_slot, UPDATED_AT), MAXIMUM_AGE).is_ok());
+        assert_eq!(
+            source.get_price(&clock_at(last_fresh_slot + 1, UPDATED_AT), MAXIMUM_AGE).err(),
+            Some(CustomError::StaleOracleData.into())
+        );
+    }
+
+    #[test]
+    fn rejects_results_older_than_the_maximum_age() {
+        let source = SwitchboardPriceSource::from_account_data(&fixture(10i128.pow(18), 0)).unwrap();
+        let at_limit = clock_at(RESULT_SLOT, UPDATED_AT + MAXIMUM_AGE as i64);
+        assert!(source.get_price(&at_limit, MAXIMUM_AGE).is_ok());
+
+        let too_late = clock_at(RESULT_SLOT, UPDATED_AT + MAXIMUM_AGE as i64 + 1);
+        assert_eq!(
+            source.get_price(&too_late, MAXIMUM_AGE).err(),
+            Some(CustomError::StaleOracleData.into())
+        );
+    }
+
+    #[test]
+    fn rejects_feeds_without_a_result() {
+        let mut data = fixture(10i128.pow(18), 0);
+        let mut feed: PullFeedAccountData = bytemuck::pod_read_unaligned(&data[8..]);
+        feed.result.slot = 0;
+        data.truncate(8);
+        data.extend_from_slice(bytemuck::bytes_of(&feed));
+
+        let source = SwitchboardPriceSource::from_account_data(&data).unwrap();
+        assert_eq!(
+            source.get_price(&clock_at(RESULT_SLOT, UPDATED_AT), MAXIMUM_AGE).err(),
+            Some(CustomError::StaleOracleData.into())
+        );
+    }
+
+    #[test]
+    fn rejects_prices_that_overflow_after_rescaling() {
+        let source = SwitchboardPriceSource::from_account_data(&fixture(i128::MAX, 0)).unwrap();
+        assert_eq!(
+            source.get_price(&clock_at(RESULT_SLOT, UPDATED_AT), MAXIMUM_AGE).err(),
+            Some(CustomError::InvalidPrice.into())
+        );
+    }
+
+    #[test]
+    fn rejects_other_account_types() {
+        let data = fixture(10i128.pow(18), 0);
+        assert_eq!(
+            SwitchboardPriceSource::from_account_data(&data[..data.len() - 1]).err(),
+            Some(CustomError::InvalidPriceAccount.into())
+        );
+
+        let mut wrong_discriminator = data;
+        wrong_discriminator[0] ^= 0xff;
+        assert_eq!(
+            SwitchboardPriceSource::from_account_data(&wrong_discriminator).err(),
+            Some(CustomError::InvalidPriceAccount.into())
+        );
+    }
+}
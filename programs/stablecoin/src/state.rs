@@ -1,11 +1,16 @@
 use anchor_lang::prelude::*;
 
+use crate::{CustomError, MAX_COLLATERAL_POSITIONS, MAX_DEBT_TIERS, SEED_FACILITATOR};
+
 #[account]
 #[derive(InitSpace, Debug)]
 pub struct Collateral {
     // I store the owner's address here
     pub depositer: Pubkey,
     
+    // The market config this vault belongs to
+    pub market: Pubkey,
+    
     // This is where I keep the SOL collateral
     pub sol_account: Pubkey,
     
@@ -24,6 +29,113 @@ pub struct Collateral {
     
     // I use this flag to know if the account has been set up
     pub is_initialized: bool,
+
+    // The publish time of the last price this position was valued at, so it can't be replayed backwards
+    pub last_price_publish_time: i64,
+
+    // The registered tokens this vault holds, one entry per mint
+    #[max_len(MAX_COLLATERAL_POSITIONS)]
+    pub positions: Vec<CollateralPosition>,
+
+    // The e-mode category this vault has opted into, or NO_EMODE_CATEGORY
+    pub emode_category: u8,
+
+    // The native stake account this vault holds the authorities of, if any
+    pub stake_account: Pubkey,
+
+    // The delegated lamports in that stake account that count as collateral
+    pub stake_lamports: u64,
+}
+
+// One registered token a vault holds as collateral
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct CollateralPosition {
+    // The token's mint, which has a CollateralConfig entry
+    pub mint: Pubkey,
+
+    // How many base units of it are in the vault
+    pub amount: u64,
+}
+
+// The ways I know how to price a listed collateral token
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CollateralKind {
+    // A liquid staking token, worth SOL/USD times its stake pool's exchange rate
+    StakePoolToken,
+
+    // A token with its own Pyth USD feed
+    OracleToken,
+}
+
+// This is my registry of the tokens I accept as collateral, one entry per mint
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct CollateralConfig {
+    // The token mint this entry covers
+    pub mint: Pubkey,
+
+    // The market config this entry belongs to
+    pub market: Pubkey,
+
+    // How I price the token
+    pub kind: CollateralKind,
+
+    // The stake pool for stake pool tokens, or the pinned Pyth price update account for oracle tokens
+    pub price_account: Pubkey,
+
+    // The Pyth feed I read from the price account (unused for stake pool tokens)
+    pub feed_id: [u8; 32],
+
+    // The mint's decimals, so I can turn base units into whole tokens
+    pub decimals: u8,
+
+    // How much of this token's value counts towards the health factor (e.g. 50%)
+    pub liquidation_threshold: u64,
+
+    // The bonus liquidators get when they seize this token (e.g. 10%)
+    pub liquidation_bonus: u64,
+
+    // The e-mode category this token belongs to, or NO_EMODE_CATEGORY
+    pub emode_category: u8,
+
+    // PDA bump for addressing
+    pub bump: u8,
+}
+
+// Once a vault's debt reaches a tier, its collateral counts for less and it needs more of it to mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct DebtTier {
+    // The debt at which this tier starts to apply; 0 marks an unused tier
+    pub min_debt: u64,
+
+    // How much every liquidation threshold is scaled down by, in basis points
+    pub threshold_haircut_bps: u64,
+
+    // How many percentage points are added to the minimum collateral ratio when minting
+    pub extra_collateral_ratio: u64,
+}
+
+// A group of correlated collateral that vaults can opt into for better terms, set by governance
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct EModeCategory {
+    // The category's id, which listed tokens and vaults refer to
+    pub id: u8,
+
+    // Whether SOL and native stake belong to the category
+    pub includes_sol: bool,
+
+    // How much of a member asset's value counts towards the health factor (e.g. 90%)
+    pub liquidation_threshold: u64,
+
+    // The bonus liquidators get when they seize a member asset (e.g. 3%)
+    pub liquidation_bonus: u64,
+
+    // The collateralization a vault in this category needs when it mints, in percent
+    pub min_collateral_ratio: u64,
+
+    // PDA bump for addressing
+    pub bump: u8,
 }
 
 // This is where I store all the protocol-wide settings
@@ -33,19 +145,197 @@ pub struct Config {
     // The admin who can update protocol parameters
     pub authority: Pubkey,
     
+    // Which market this is; every other account of the market is derived from this config's address
+    pub market_id: u64,
+    
     // The address of my stablecoin token mint
     pub mint_account: Pubkey,
     
+    // Voting power comes from this separate token, so borrowers don't vote with their debt
+    pub governance_mint: Pubkey,
+    
     // I use this to determine when positions can be liquidated (e.g. 50%)
     pub liquidation_threshold: u64,
     
     // Liquidators get this bonus to incentivize them (e.g. 10%)
     pub liquidation_bonus: u64,
     
-    // The minimum health factor before liquidation (usually 1.0)
-    pub min_health_factor: u64,
+    // The minimum health factor before liquidation, in basis points (10_000 = 1.0)
+    pub min_health_factor_bps: u64,
+    
+    // Stricter requirements for large vaults, which are harder to liquidate without moving the market
+    pub debt_tiers: [DebtTier; MAX_DEBT_TIERS],
+    
+    // Stablecoin owed by every vault in the market, mint fees included - those are minted to the treasury,
+    // so together with facilitator_debt this always matches the mint's supply
+    pub vault_debt: u64,
+    
+    // Stablecoin minted by facilitators and not yet burned back
+    pub facilitator_debt: u64,
+    
+    // The longest governance tokens can be locked for; a lock this long votes at full weight
+    pub max_lock_duration: i64,
+    
+    // Once governance shuts the market down, nothing new is minted and governance locks can be withdrawn early
+    pub emergency_shutdown: bool,
     
     // More PDA bumps for Solana's addressing
     pub bump: u8,
     pub bump_mint_account: u8,
+    pub bump_governance_mint: u8,
+}
+
+// The oracle networks (or fixed peg) I know how to read a price from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum OracleSource {
+    Pyth,
+    Switchboard,
+    Fixed,
+}
+
+// This is where I store which price adapter the protocol reads from
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct OracleConfig {
+    // Which adapter I use to read the collateral price
+    pub source: OracleSource,
+
+    // The Pyth price update account I accept when the source is Pyth
+    pub pyth_price_account: Pubkey,
+
+    // The Switchboard pull feed I accept when the source is Switchboard
+    pub switchboard_feed: Pubkey,
+
+    // The governance-set price used when the source is Fixed (scaled by 10^fixed_exponent)
+    pub fixed_price: i64,
+    pub fixed_exponent: i32,
+
+    // The Pyth account and feed pricing the market's currency in USD (e.g. EUR/USD);
+    // the default key means the market is denominated in USD
+    pub target_price_account: Pubkey,
+    pub target_feed_id: [u8; 32],
+
+    // I reject prices whose confidence interval is wider than this share of the price (in basis points)
+    pub max_confidence_ratio_bps: u64,
+
+    // How many confidence intervals I shift prices by when valuing conservatively (10_000 = 1x)
+    pub confidence_multiplier_bps: u64,
+
+    // The circuit breaker trips if the price moves more than this within the window (in basis points)
+    pub max_price_move_bps: u64,
+
+    // How long the circuit breaker's comparison window lasts (in seconds)
+    pub price_move_window: i64,
+
+    // PDA bump for addressing
+    pub bump: u8,
+}
+
+// This is where I remember what the oracle told me, so I can spot abnormal moves
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct OracleState {
+    // The last spot price I accepted, normalized to PRICE_DECIMALS
+    pub last_price: u64,
+
+    // When that price was published
+    pub last_price_timestamp: i64,
+
+    // The price at the start of the current circuit breaker window
+    pub reference_price: u64,
+
+    // When the current window started
+    pub reference_timestamp: i64,
+
+    // While this is set, minting and collateral withdrawal are paused
+    pub circuit_breaker_tripped: bool,
+
+    // When the circuit breaker last tripped
+    pub tripped_at: i64,
+
+    // While this is set, every oracle was stale and only de-risking operations are allowed
+    pub fallback_mode: bool,
+
+    // PDA bump for addressing
+    pub bump: u8,
+}
+
+impl Config {
+    // Everything the market has minted and not yet burned, whoever minted it
+    pub fn total_debt(&self) -> Result<u64> {
+        self.vault_debt
+            .checked_add(self.facilitator_debt)
+            .ok_or(error!(CustomError::MathOverflow))
+    }
+}
+
+// An external program governance trusts to mint the stablecoin directly (e.g. a PSM or an RWA desk)
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Facilitator {
+    // The market this facilitator mints into
+    pub market: Pubkey,
+
+    // The facilitator program governance registered
+    pub program_id: Pubkey,
+
+    // That program's PDA, which has to sign every mint and burn through CPI
+    pub facilitator: Pubkey,
+
+    // The most this facilitator can have outstanding at once
+    pub bucket_capacity: u64,
+
+    // What this facilitator has minted and not yet burned
+    pub bucket_level: u64,
+
+    pub bump: u8,
+}
+
+impl Facilitator {
+    // The PDA a facilitator program signs with in a market; being off the curve, no keypair can sign for it
+    pub fn signer(program_id: &Pubkey, market: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[SEED_FACILITATOR, market.as_ref()], program_id).0
+    }
+}
+
+// The bar one tier of proposals has to clear, in basis points
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, InitSpace)]
+pub struct TierThresholds {
+    pub quorum_bps: u64,
+    pub approval_bps: u64,
+}
+
+// The market's governance rules, which governance itself can amend within fixed bounds
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct GovernanceConfig {
+    pub market: Pubkey,
+
+    // How long each proposal is open for voting (in seconds)
+    pub voting_period: i64,
+
+    // How long a passed proposal waits before it can execute (in seconds)
+    pub execution_delay: i64,
+
+    // How long after the delay a passed proposal can still be executed (in seconds)
+    pub execution_window: i64,
+
+    // Can veto proposals until they execute, e.g. a security council multisig; the default key means nobody can
+    pub guardian: Pubkey,
+
+    // The voting power needed to create a proposal
+    pub proposal_threshold: u64,
+
+    // Quorum and approval for housekeeping, risk and critical proposals
+    pub standard: TierThresholds,
+    pub risk: TierThresholds,
+    pub critical: TierThresholds,
+
+    // Proposals created so far; each one is numbered from this, since ids are reused once a proposal is closed
+    pub proposal_count: u64,
+
+    pub bump: u8,
+
+    // Bump of the system-owned PDA that signs instructions run by governance
+    pub authority_bump: u8,
 }
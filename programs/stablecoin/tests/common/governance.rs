@@ -0,0 +1,269 @@
+// Shortcuts for driving a market's governance: voters, proposals and the instructions that move them along
+use anchor_lang::{
+    prelude::*,
+    solana_program::{entrypoint::ProgramResult, instruction::AccountMeta, native_token::LAMPORTS_PER_SOL},
+};
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, token_2022::spl_token_2022};
+use ::stablecoin::{
+    accounts, instruction, Checkpoint, GovernanceConfig, Proposal, ProposalType, VoteRecord, VoteType, VoterWeight,
+    SEED_GOVERNANCE, SEED_VOTER_WEIGHT, SEED_VOTE_RECORD,
+};
+
+use super::{slot, now, Bank, Market};
+
+impl Market {
+    pub fn voter_weight(&self, owner: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[SEED_VOTER_WEIGHT, self.config.as_ref(), owner.as_ref()], &::stablecoin::ID).0
+    }
+
+    pub fn proposal(&self, id: u64) -> Pubkey {
+        Pubkey::find_program_address(&[SEED_GOVERNANCE, self.config.as_ref(), id.to_le_bytes().as_ref()], &::stablecoin::ID).0
+    }
+
+    pub fn vote_record(&self, id: u64, voter: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[SEED_VOTE_RECORD, self.proposal(id).as_ref(), voter.as_ref()], &::stablecoin::ID).0
+    }
+
+    // The token account holding a voter's locked tokens
+    pub fn voter_vault(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(&self.voter_weight(owner), &self.governance_mint, &spl_token_2022::ID)
+    }
+
+    pub fn governance_config(&self, bank: &Bank) -> GovernanceConfig {
+        bank.anchor_account(&self.governance_config)
+    }
+
+    // Rewrites the governance rules directly, as an UpdateGovernanceConfig proposal would
+    pub fn set_governance_config(&self, bank: &mut Bank, governance_config: &GovernanceConfig) {
+        bank.set_anchor_account(self.governance_config, governance_config, 8 + GovernanceConfig::INIT_SPACE);
+    }
+
+    pub fn proposal_account(&self, bank: &Bank, id: u64) -> Proposal {
+        bank.anchor_account(&self.proposal(id))
+    }
+
+    pub fn vote_record_account(&self, bank: &Bank, id: u64, voter: &Pubkey) -> VoteRecord {
+        bank.anchor_account(&self.vote_record(id, voter))
+    }
+
+    pub fn set_governance_supply(&self, bank: &mut Bank, supply: u64) {
+        bank.set_mint(self.governance_mint, self.governance_mint, supply, ::stablecoin::GOVERNANCE_MINT_DECIMALS);
+    }
+
+    // A new voter with the given balance locked until unlock_in seconds from now, checkpointed at the current slot
+    pub fn add_voter(&self, bank: &mut Bank, balance: u64, unlock_in: i64) -> Pubkey {
+        let owner = Pubkey::new_unique();
+        bank.fund(owner, 10 * LAMPORTS_PER_SOL);
+        let (voter_weight, bump) = Pubkey::find_program_address(
+            &[SEED_VOTER_WEIGHT, self.config.as_ref(), owner.as_ref()],
+            &::stablecoin::ID,
+        );
+        bank.set_anchor_account(
+            voter_weight,
+            &VoterWeight {
+                market: self.config,
+                owner,
+                checkpoints: vec![Checkpoint {
+                    slot: slot(),
+                    balance,
+                    unlock_at: now() + unlock_in,
+                    delegate: Pubkey::default(),
+                }],
+                bump,
+            },
+            8 + VoterWeight::INIT_SPACE,
+        );
+        bank.set_token_account(self.voter_vault(&owner), self.governance_mint, voter_weight, balance);
+        owner
+    }
+
+    // Someone holding governance tokens in their wallet, without a lock
+    pub fn add_holder(&self, bank: &mut Bank, amount: u64) -> (Pubkey, Pubkey) {
+        let owner = Pubkey::new_unique();
+        bank.fund(owner, 10 * LAMPORTS_PER_SOL);
+        let token_account = Pubkey::new_unique();
+        bank.set_token_account(token_account, self.governance_mint, owner, amount);
+        (owner, token_account)
+    }
+
+    pub fn deposit(&self, bank: &mut Bank, owner: Pubkey, owner_token_account: Pubkey, amount: u64) -> ProgramResult {
+        // I stand in for the associated token program, which the bank can't run
+        let voter_vault = self.voter_vault(&owner);
+        if !bank.exists(&voter_vault) {
+            bank.set_token_account(voter_vault, self.governance_mint, self.voter_weight(&owner), 0);
+        }
+        bank.process(
+            accounts::DepositGovernanceTokens {
+                owner,
+                config: self.config,
+                voter_weight: self.voter_weight(&owner),
+                governance_mint: self.governance_mint,
+                owner_token_account,
+                voter_weight_vault: voter_vault,
+                token_program: spl_token_2022::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: System::id(),
+            },
+            instruction::DepositGovernanceTokens { amount },
+        )
+    }
+
+    pub fn withdraw(&self, bank: &mut Bank, owner: Pubkey, owner_token_account: Pubkey, amount: u64) -> ProgramResult {
+        bank.process(
+            accounts::WithdrawGovernanceTokens {
+                owner,
+                config: self.config,
+                voter_weight: self.voter_weight(&owner),
+                governance_mint: self.governance_mint,
+                owner_token_account,
+                voter_weight_vault: self.voter_vault(&owner),
+                token_program: spl_token_2022::ID,
+            },
+            instruction::WithdrawGovernanceTokens { amount },
+        )
+    }
+
+    pub fn extend_lock(&self, bank: &mut Bank, owner: Pubkey, lock_duration: i64) -> ProgramResult {
+        bank.process(
+            accounts::ExtendLock { owner, config: self.config, voter_weight: self.voter_weight(&owner) },
+            instruction::ExtendLock { lock_duration },
+        )
+    }
+
+    pub fn create_proposal(&self, bank: &mut Bank, proposer: Pubkey, id: u64, proposal_type: ProposalType) -> ProgramResult {
+        bank.process(
+            accounts::CreateProposal {
+                proposer,
+                config: self.config,
+                governance_config: self.governance_config,
+                governance_mint: self.governance_mint,
+                proposer_weight: self.voter_weight(&proposer),
+                proposal: self.proposal(id),
+                system_program: System::id(),
+            },
+            instruction::CreateProposal { proposal_id: id, description: format!("Proposal {id}"), proposal_type },
+        )
+    }
+
+    pub fn vote(&self, bank: &mut Bank, voter: Pubkey, id: u64, vote_type: VoteType) -> ProgramResult {
+        self.vote_for_delegators(bank, voter, id, vote_type, &[])
+    }
+
+    // Votes with the voter's own power, if they've locked any, and with the power each delegator handed them
+    pub fn vote_for_delegators(
+        &self,
+        bank: &mut Bank,
+        voter: Pubkey,
+        id: u64,
+        vote_type: VoteType,
+        delegators: &[Pubkey],
+    ) -> ProgramResult {
+        let remaining_accounts = delegators
+            .iter()
+            .flat_map(|delegator| {
+                [
+                    AccountMeta::new_readonly(self.voter_weight(delegator), false),
+                    AccountMeta::new(self.vote_record(id, delegator), false),
+                ]
+            })
+            .collect();
+        let voter_weight = self.voter_weight(&voter);
+        bank.process_with_remaining(
+            accounts::VoteOnProposal {
+                voter,
+                config: self.config,
+                voter_weight: bank.exists(&voter_weight).then_some(voter_weight),
+                proposal: self.proposal(id),
+                vote_record: self.vote_record(id, &voter),
+                system_program: System::id(),
+            },
+            remaining_accounts,
+            instruction::VoteOnProposal { proposal_id: id, vote_type },
+        )
+    }
+
+    pub fn withdraw_vote(&self, bank: &mut Bank, voter: Pubkey, id: u64) -> ProgramResult {
+        bank.process(
+            accounts::WithdrawVote {
+                voter,
+                config: self.config,
+                proposal: self.proposal(id),
+                vote_record: self.vote_record(id, &voter),
+            },
+            instruction::WithdrawVote { proposal_id: id },
+        )
+    }
+
+    pub fn delegate(&self, bank: &mut Bank, owner: Pubkey, delegate: Pubkey) -> ProgramResult {
+        bank.process(
+            accounts::Delegate { owner, config: self.config, voter_weight: self.voter_weight(&owner) },
+            instruction::Delegate { delegate },
+        )
+    }
+
+    pub fn undelegate(&self, bank: &mut Bank, owner: Pubkey) -> ProgramResult {
+        bank.process(
+            accounts::Undelegate { owner, config: self.config, voter_weight: self.voter_weight(&owner) },
+            instruction::Undelegate {},
+        )
+    }
+
+    pub fn finalize(&self, bank: &mut Bank, id: u64) -> ProgramResult {
+        let payer = Pubkey::new_unique();
+        bank.process(
+            accounts::FinalizeProposal {
+                payer,
+                config: self.config,
+                governance_config: self.governance_config,
+                proposal: self.proposal(id),
+            },
+            instruction::FinalizeProposal { proposal_id: id },
+        )
+    }
+
+    // The accounts every proposal executes with, from a funded executor and with none of the optional ones
+    pub fn execute_accounts(&self, bank: &mut Bank, id: u64) -> accounts::ExecuteProposal {
+        let executor = Pubkey::new_unique();
+        bank.fund(executor, LAMPORTS_PER_SOL);
+        accounts::ExecuteProposal {
+            executor,
+            config: self.config,
+            oracle_config: self.oracle_config,
+            oracle_state: self.oracle_state,
+            governance_config: self.governance_config,
+            proposal: self.proposal(id),
+            emode_category: None,
+            collateral_config: None,
+            facilitator: None,
+            governance_mint: None,
+            recipient_token_account: None,
+            token_program: None,
+            system_program: System::id(),
+        }
+    }
+
+    // Executes a proposal that doesn't need any of the optional accounts, with the given remaining accounts
+    pub fn execute(&self, bank: &mut Bank, id: u64, remaining_accounts: Vec<AccountMeta>) -> ProgramResult {
+        let accounts = self.execute_accounts(bank, id);
+        bank.process_with_remaining(accounts, remaining_accounts, instruction::ExecuteProposal { proposal_id: id })
+    }
+
+    pub fn cancel(&self, bank: &mut Bank, canceller: Pubkey, id: u64) -> ProgramResult {
+        bank.process(
+            accounts::CancelProposal {
+                canceller,
+                config: self.config,
+                governance_config: self.governance_config,
+                proposal: self.proposal(id),
+            },
+            instruction::CancelProposal { proposal_id: id },
+        )
+    }
+
+    pub fn close_proposal(&self, bank: &mut Bank, proposer: Pubkey, id: u64) -> ProgramResult {
+        bank.process(
+            accounts::CloseProposal { proposer, config: self.config, proposal: self.proposal(id) },
+            instruction::CloseProposal { proposal_id: id },
+        )
+    }
+}
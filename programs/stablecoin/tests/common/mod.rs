@@ -0,0 +1,438 @@
+// An in-process stand-in for the runtime: I keep every account in memory, run instructions through
+// the program's entrypoint, and route its CPIs to the token program, a minimal system program and
+// back into the program itself
+#![allow(dead_code)]
+
+use std::{cell::{Cell, RefCell}, collections::HashMap, sync::Once};
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+        instruction::{AccountMeta, Instruction},
+        native_token::LAMPORTS_PER_SOL,
+        program_option::COption,
+        program_pack::Pack,
+        program_stubs::{self, SyscallStubs},
+    },
+    AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
+use ::stablecoin::*;
+
+pub mod governance;
+
+pub const MARKET_ID: u64 = 0;
+
+thread_local! {
+    static CLOCK: Cell<(u64, i64)> = const { Cell::new((1, 1_700_000_000)) };
+    static CALLERS: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let (slot, unix_timestamp) = CLOCK.get();
+        let clock = Clock { slot, unix_timestamp, ..Clock::default() };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = CALLERS.with(|callers| *callers.borrow().last().unwrap());
+        let pda_signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut infos = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer && !pda_signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            // Every account info handed to me is backed by memory the bank leaked
+            let mut info: AccountInfo<'static> = unsafe { std::mem::transmute(info.clone()) };
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            infos.push(info);
+        }
+        invoke(&instruction.program_id, infos, &instruction.data)
+    }
+}
+
+fn invoke(program_id: &Pubkey, infos: Vec<AccountInfo<'static>>, data: &[u8]) -> ProgramResult {
+    let infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+    CALLERS.with(|callers| callers.borrow_mut().push(*program_id));
+    let result = if *program_id == ::stablecoin::ID {
+        ::stablecoin::entry(program_id, infos, data)
+    } else if *program_id == spl_token_2022::ID {
+        spl_token_2022::processor::Processor::process(program_id, infos, data)
+    } else if *program_id == System::id() {
+        process_system_instruction(infos, data)
+    } else {
+        Err(ProgramError::IncorrectProgramId)
+    };
+    CALLERS.with(|callers| callers.borrow_mut().pop());
+    result
+}
+
+// Just the system instructions Anchor uses to create accounts and move lamports
+fn process_system_instruction(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let pubkey_at = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+    match u32::from_le_bytes(data[..4].try_into().unwrap()) {
+        // CreateAccount
+        0 => {
+            transfer_lamports(&infos[0], &infos[1], u64_at(4))?;
+            set_data(&infos[1], vec![0; u64_at(12) as usize]);
+            infos[1].assign(&pubkey_at(20));
+        }
+        // Assign
+        1 => infos[0].assign(&pubkey_at(4)),
+        // Transfer
+        2 => transfer_lamports(&infos[0], &infos[1], u64_at(4))?,
+        // Allocate
+        8 => set_data(&infos[0], vec![0; u64_at(4) as usize]),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+    Ok(())
+}
+
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    **from.try_borrow_mut_lamports()? = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+// Like the runtime, I leave room for the data's length in front of it and for it to grow behind it, since
+// AccountInfo::realloc writes to both
+fn set_data(info: &AccountInfo, data: Vec<u8>) {
+    let len = data.len();
+    let mut buffer = vec![0; 8 + len + MAX_PERMITTED_DATA_INCREASE];
+    buffer[..8].copy_from_slice(&(len as u64).to_le_bytes());
+    buffer[8..8 + len].copy_from_slice(&data);
+    let buffer = Box::leak(buffer.into_boxed_slice());
+    *info.data.borrow_mut() = &mut buffer[8..8 + len];
+}
+
+// AccountInfo::realloc reads the account's original length from just in front of its key
+#[repr(C)]
+struct Key {
+    original_data_len: u32,
+    key: Pubkey,
+}
+
+pub fn set_clock(slot: u64, unix_timestamp: i64) {
+    CLOCK.set((slot, unix_timestamp));
+}
+
+pub fn now() -> i64 {
+    CLOCK.get().1
+}
+
+pub fn slot() -> u64 {
+    CLOCK.get().0
+}
+
+// Moves the clock forward, a slot at a time so checkpoints taken before and after can be told apart
+pub fn advance_clock(seconds: i64) {
+    let (slot, unix_timestamp) = CLOCK.get();
+    CLOCK.set((slot + 1, unix_timestamp + seconds));
+}
+
+pub fn error(error: CustomError) -> ProgramError {
+    anchor_lang::error::Error::from(error).into()
+}
+
+pub struct Bank {
+    accounts: HashMap<Pubkey, AccountInfo<'static>>,
+}
+
+impl Bank {
+    pub fn new() -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Stubs));
+        });
+
+        set_clock(1, 1_700_000_000);
+        let mut bank = Self { accounts: HashMap::new() };
+        for program_id in [::stablecoin::ID, spl_token_2022::ID, System::id(), anchor_spl::associated_token::ID] {
+            let mut info = bank.account_info(program_id).clone();
+            info.executable = true;
+            bank.accounts.insert(program_id, info);
+        }
+        bank
+    }
+
+    // Looks up an account, creating an empty system account the first time it's used
+    fn account_info(&mut self, key: Pubkey) -> &AccountInfo<'static> {
+        self.accounts.entry(key).or_insert_with(|| {
+            // I never hold an account to its original length, so realloc can always grow it
+            let key = Box::leak(Box::new(Key { original_data_len: u32::MAX, key }));
+            let info = AccountInfo::new(
+                &key.key,
+                false,
+                false,
+                Box::leak(Box::new(0)),
+                &mut [],
+                Box::leak(Box::new(System::id())),
+                false,
+                0,
+            );
+            set_data(&info, vec![]);
+            info
+        })
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) {
+        let info = self.account_info(key);
+        **info.lamports.borrow_mut() = lamports;
+        set_data(info, data);
+        info.assign(&owner);
+    }
+
+    pub fn fund(&mut self, key: Pubkey, lamports: u64) {
+        let info = self.account_info(key);
+        **info.lamports.borrow_mut() += lamports;
+    }
+
+    pub fn set_anchor_account<T: AccountSerialize + Owner>(&mut self, key: Pubkey, account: &T, space: usize) {
+        let mut data = Vec::with_capacity(space);
+        account.try_serialize(&mut data).unwrap();
+        data.resize(space, 0);
+        self.set_account(key, T::owner(), Rent::default().minimum_balance(space), data);
+    }
+
+    pub fn set_mint(&mut self, key: Pubkey, authority: Pubkey, supply: u64, decimals: u8) {
+        let mint = Mint {
+            mint_authority: COption::Some(authority),
+            supply,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::Some(authority),
+        };
+        let mut data = vec![0; Mint::LEN];
+        mint.pack_into_slice(&mut data);
+        self.set_account(key, spl_token_2022::ID, Rent::default().minimum_balance(Mint::LEN), data);
+    }
+
+    pub fn set_token_account(&mut self, key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) {
+        let account = TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        account.pack_into_slice(&mut data);
+        self.set_account(key, spl_token_2022::ID, Rent::default().minimum_balance(TokenAccount::LEN), data);
+    }
+
+    pub fn exists(&self, key: &Pubkey) -> bool {
+        self.accounts.get(key).is_some_and(|info| !info.data_is_empty())
+    }
+
+    pub fn anchor_account<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        T::try_deserialize(&mut &self.accounts[key].data.borrow()[..]).unwrap()
+    }
+
+    pub fn mint(&self, key: &Pubkey) -> Mint {
+        Mint::unpack_from_slice(&self.accounts[key].data.borrow()[..Mint::LEN]).unwrap()
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        TokenAccount::unpack_from_slice(&self.accounts[key].data.borrow()[..TokenAccount::LEN]).unwrap().amount
+    }
+
+    pub fn process(&mut self, accounts: impl ToAccountMetas, instruction: impl InstructionData) -> ProgramResult {
+        self.process_with_remaining(accounts, vec![], instruction)
+    }
+
+    // Runs one instruction as its own transaction: if it fails, every account is rolled back
+    pub fn process_with_remaining(
+        &mut self,
+        accounts: impl ToAccountMetas,
+        remaining_accounts: Vec<AccountMeta>,
+        instruction: impl InstructionData,
+    ) -> ProgramResult {
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend(remaining_accounts);
+        let infos = metas
+            .iter()
+            .map(|meta| {
+                let mut info = self.account_info(meta.pubkey).clone();
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                info
+            })
+            .collect();
+
+        let snapshot = self
+            .accounts
+            .iter()
+            .map(|(key, info)| (*key, (info.lamports(), info.data.borrow().to_vec(), *info.owner)))
+            .collect::<Vec<_>>();
+        let result = invoke(&::stablecoin::ID, infos, &instruction.data());
+        if result.is_err() {
+            for (key, (lamports, data, owner)) in snapshot {
+                let info = &self.accounts[&key];
+                **info.lamports.borrow_mut() = lamports;
+                set_data(info, data);
+                info.assign(&owner);
+            }
+        }
+        result
+    }
+}
+
+// The addresses of one market, derived the same way the program derives them
+pub struct Market {
+    pub authority: Pubkey,
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    pub governance_mint: Pubkey,
+    pub governance_config: Pubkey,
+    pub governance_authority: Pubkey,
+    pub oracle_config: Pubkey,
+    pub oracle_state: Pubkey,
+}
+
+impl Market {
+    pub fn new(bank: &mut Bank) -> Self {
+        let authority = Pubkey::new_unique();
+        let (config, bump) =
+            Pubkey::find_program_address(&[SEED_CONFIG_ACCOUNT, MARKET_ID.to_le_bytes().as_ref()], &::stablecoin::ID);
+        let (mint, bump_mint_account) = Pubkey::find_program_address(&[SEED_MINT_ACCOUNT, config.as_ref()], &::stablecoin::ID);
+        let (governance_mint, bump_governance_mint) =
+            Pubkey::find_program_address(&[SEED_GOVERNANCE_MINT, config.as_ref()], &::stablecoin::ID);
+        let (governance_config, governance_bump) =
+            Pubkey::find_program_address(&[SEED_GOVERNANCE, config.as_ref()], &::stablecoin::ID);
+        let (governance_authority, authority_bump) =
+            Pubkey::find_program_address(&[SEED_GOVERNANCE_AUTHORITY, config.as_ref()], &::stablecoin::ID);
+
+        let (oracle_config, oracle_config_bump) =
+            Pubkey::find_program_address(&[SEED_ORACLE_CONFIG, config.as_ref()], &::stablecoin::ID);
+        let (oracle_state, oracle_state_bump) =
+            Pubkey::find_program_address(&[SEED_ORACLE_STATE, config.as_ref()], &::stablecoin::ID);
+
+        bank.fund(authority, 100 * LAMPORTS_PER_SOL);
+        bank.set_mint(mint, mint, 0, MINT_DECIMALS);
+        bank.set_mint(governance_mint, governance_mint, 0, GOVERNANCE_MINT_DECIMALS);
+        bank.set_anchor_account(
+            config,
+            &Config {
+                authority,
+                market_id: MARKET_ID,
+                mint_account: mint,
+                governance_mint,
+                liquidation_threshold: LIQUIDATION_THRESHOLD,
+                liquidation_bonus: LIQUIDATION_BONUS,
+                min_health_factor_bps: MIN_HEALTH_FACTOR_BPS,
+                debt_tiers: [DebtTier::default(); MAX_DEBT_TIERS],
+                vault_debt: 0,
+                facilitator_debt: 0,
+                max_lock_duration: DEFAULT_MAX_LOCK_DURATION,
+                emergency_shutdown: false,
+                bump,
+                bump_mint_account,
+                bump_governance_mint,
+            },
+            8 + Config::INIT_SPACE,
+        );
+        bank.set_anchor_account(
+            governance_config,
+            &GovernanceConfig {
+                market: config,
+                voting_period: DEFAULT_VOTING_PERIOD,
+                execution_delay: DEFAULT_EXECUTION_DELAY,
+                execution_window: DEFAULT_EXECUTION_WINDOW,
+                guardian: authority,
+                proposal_threshold: DEFAULT_PROPOSAL_THRESHOLD,
+                standard: TierThresholds { quorum_bps: STANDARD_QUORUM_BPS, approval_bps: STANDARD_APPROVAL_BPS },
+                risk: TierThresholds { quorum_bps: RISK_QUORUM_BPS, approval_bps: RISK_APPROVAL_BPS },
+                critical: TierThresholds { quorum_bps: CRITICAL_QUORUM_BPS, approval_bps: CRITICAL_APPROVAL_BPS },
+                proposal_count: 0,
+                bump: governance_bump,
+                authority_bump,
+            },
+            8 + GovernanceConfig::INIT_SPACE,
+        );
+
+        bank.set_anchor_account(
+            oracle_config,
+            &OracleConfig {
+                source: OracleSource::Fixed,
+                pyth_price_account: Pubkey::default(),
+                switchboard_feed: Pubkey::default(),
+                fixed_price: 1,
+                fixed_exponent: 0,
+                target_price_account: Pubkey::default(),
+                target_feed_id: [0; 32],
+                max_confidence_ratio_bps: DEFAULT_MAX_CONFIDENCE_RATIO_BPS,
+                confidence_multiplier_bps: DEFAULT_CONFIDENCE_MULTIPLIER_BPS,
+                max_price_move_bps: DEFAULT_MAX_PRICE_MOVE_BPS,
+                price_move_window: DEFAULT_PRICE_MOVE_WINDOW,
+                bump: oracle_config_bump,
+            },
+            8 + OracleConfig::INIT_SPACE,
+        );
+        bank.set_anchor_account(
+            oracle_state,
+            &OracleState {
+                last_price: 0,
+                last_price_timestamp: 0,
+                reference_price: 0,
+                reference_timestamp: 0,
+                circuit_breaker_tripped: false,
+                tripped_at: 0,
+                fallback_mode: false,
+                bump: oracle_state_bump,
+            },
+            8 + OracleState::INIT_SPACE,
+        );
+
+        Self {
+            authority,
+            config,
+            mint,
+            governance_mint,
+            governance_config,
+            governance_authority,
+            oracle_config,
+            oracle_state,
+        }
+    }
+
+    pub fn config(&self, bank: &Bank) -> Config {
+        bank.anchor_account(&self.config)
+    }
+
+    // Rewrites the config directly, for state that would otherwise take a proposal to reach
+    pub fn set_config(&self, bank: &mut Bank, config: &Config) {
+        bank.set_anchor_account(self.config, config, 8 + Config::INIT_SPACE);
+    }
+}
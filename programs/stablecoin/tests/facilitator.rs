@@ -0,0 +1,161 @@
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::entrypoint::ProgramResult};
+use anchor_spl::token_2022::spl_token_2022;
+use common::{error, Bank, Market};
+use stablecoin::{accounts, instruction, CustomError, Facilitator, SEED_FACILITATOR};
+
+struct Setup {
+    bank: Bank,
+    market: Market,
+    program_id: Pubkey,
+    facilitator: Pubkey,
+    facilitator_account: Pubkey,
+    token_account: Pubkey,
+}
+
+fn setup(bucket_capacity: u64) -> Setup {
+    let mut bank = Bank::new();
+    let market = Market::new(&mut bank);
+
+    // I register the facilitator the way a SetFacilitator proposal would; the bank lets me sign for its PDA
+    // the way its program would through CPI
+    let program_id = Pubkey::new_unique();
+    let facilitator = Facilitator::signer(&program_id, &market.config);
+    let (facilitator_account, bump) = Pubkey::find_program_address(
+        &[SEED_FACILITATOR, market.config.as_ref(), program_id.as_ref()],
+        &::stablecoin::ID,
+    );
+    bank.set_anchor_account(
+        facilitator_account,
+        &Facilitator { market: market.config, program_id, facilitator, bucket_capacity, bucket_level: 0, bump },
+        8 + Facilitator::INIT_SPACE,
+    );
+    let token_account = Pubkey::new_unique();
+    bank.set_token_account(token_account, market.mint, facilitator, 0);
+
+    Setup { bank, market, program_id, facilitator, facilitator_account, token_account }
+}
+
+impl Setup {
+    fn mint(&mut self, amount: u64) -> ProgramResult {
+        self.bank.process(
+            accounts::FacilitatorMint {
+                facilitator: self.facilitator,
+                config_account: self.market.config,
+                facilitator_account: self.facilitator_account,
+                mint_account: self.market.mint,
+                token_account: self.token_account,
+                token_program: spl_token_2022::ID,
+            },
+            instruction::FacilitatorMint { amount },
+        )
+    }
+
+    fn burn(&mut self, amount: u64) -> ProgramResult {
+        self.bank.process(
+            accounts::FacilitatorBurn {
+                facilitator: self.facilitator,
+                config_account: self.market.config,
+                facilitator_account: self.facilitator_account,
+                mint_account: self.market.mint,
+                token_account: self.token_account,
+                token_program: spl_token_2022::ID,
+            },
+            instruction::FacilitatorBurn { amount },
+        )
+    }
+
+    // The bucket level, the market's facilitator debt and the stablecoin's supply
+    fn levels(&self) -> (u64, u64, u64) {
+        let facilitator_account: Facilitator = self.bank.anchor_account(&self.facilitator_account);
+        (
+            facilitator_account.bucket_level,
+            self.market.config(&self.bank).facilitator_debt,
+            self.bank.mint(&self.market.mint).supply,
+        )
+    }
+}
+
+#[test]
+fn mint_and_burn_move_the_bucket_and_the_market_debt_together() {
+    let mut setup = setup(1_000);
+
+    setup.mint(600).unwrap();
+    assert_eq!(setup.levels(), (600, 600, 600));
+    assert_eq!(setup.bank.token_balance(&setup.token_account), 600);
+
+    setup.burn(250).unwrap();
+    assert_eq!(setup.levels(), (350, 350, 350));
+    assert_eq!(setup.bank.token_balance(&setup.token_account), 350);
+    assert_eq!(setup.market.config(&setup.bank).total_debt().unwrap(), 350);
+}
+
+#[test]
+fn minting_past_the_bucket_capacity_fails() {
+    let mut setup = setup(1_000);
+
+    setup.mint(1_000).unwrap();
+    assert_eq!(setup.mint(1), Err(error(CustomError::FacilitatorCapacityExceeded)));
+    assert_eq!(setup.levels(), (1_000, 1_000, 1_000));
+
+    // Burning frees the capacity up again
+    setup.burn(400).unwrap();
+    setup.mint(400).unwrap();
+    assert_eq!(setup.levels(), (1_000, 1_000, 1_000));
+}
+
+#[test]
+fn a_facilitator_cannot_burn_more_than_it_minted() {
+    let mut setup = setup(1_000);
+
+    setup.mint(300).unwrap();
+    assert_eq!(setup.burn(301), Err(error(CustomError::FacilitatorBurnExceedsLevel)));
+    assert_eq!(setup.levels(), (300, 300, 300));
+}
+
+#[test]
+fn only_the_registered_facilitator_can_mint() {
+    let mut setup = setup(1_000);
+
+    // Without its signature the mint is refused, and nothing moves
+    let metas = accounts::FacilitatorMint {
+        facilitator: setup.facilitator,
+        config_account: setup.market.config,
+        facilitator_account: setup.facilitator_account,
+        mint_account: setup.market.mint,
+        token_account: setup.token_account,
+        token_program: spl_token_2022::ID,
+    }
+    .to_account_metas(None)
+    .into_iter()
+    .map(|mut meta| {
+        meta.is_signer = false;
+        meta
+    })
+    .collect::<Vec<_>>();
+    assert!(setup.bank.process(metas, instruction::FacilitatorMint { amount: 100 }).is_err());
+    assert_eq!(setup.levels(), (0, 0, 0));
+}
+
+#[test]
+fn only_the_facilitator_programs_pda_can_use_its_bucket() {
+    let mut setup = setup(1_000);
+
+    // Neither a keypair nor the program's own id will do, even signing
+    for signer in [Pubkey::new_unique(), setup.program_id] {
+        let result = setup.bank.process(
+            accounts::FacilitatorMint {
+                facilitator: signer,
+                config_account: setup.market.config,
+                facilitator_account: setup.facilitator_account,
+                mint_account: setup.market.mint,
+                token_account: setup.token_account,
+                token_program: spl_token_2022::ID,
+            },
+            instruction::FacilitatorMint { amount: 100 },
+        );
+        assert_eq!(result, Err(Error::from(ErrorCode::ConstraintHasOne).into()));
+    }
+    assert_eq!(setup.levels(), (0, 0, 0));
+}
@@ -0,0 +1,771 @@
+mod common;
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{entrypoint::ProgramResult, instruction::AccountMeta},
+    InstructionData,
+};
+use common::{advance_clock, error, Bank, Market};
+use stablecoin::{
+    accounts, instruction, CustomError, Facilitator, GovernanceAccountMeta, GovernanceInstruction, ProposalStatus,
+    ProposalType, VoteType, DEFAULT_EXECUTION_DELAY, DEFAULT_EXECUTION_WINDOW, DEFAULT_MAX_LOCK_DURATION,
+    DEFAULT_VOTING_PERIOD, EModeCategory, NO_EMODE_CATEGORY, SEED_EMODE_CATEGORY, SEED_FACILITATOR,
+};
+
+const SUPPLY: u64 = 1_000_000;
+
+// A lock running past the longest lock duration votes at full weight, so its power is its balance
+const FULL_LOCK: i64 = DEFAULT_MAX_LOCK_DURATION + 1;
+
+fn setup() -> (Bank, Market) {
+    let mut bank = Bank::new();
+    let market = Market::new(&mut bank);
+    market.set_governance_supply(&mut bank, SUPPLY);
+    (bank, market)
+}
+
+// Votes the proposal through with the given voter and finalizes it once voting ends
+fn pass(bank: &mut Bank, market: &Market, voter: Pubkey, id: u64) {
+    market.vote(bank, voter, id, VoteType::For).unwrap();
+    advance_clock(DEFAULT_VOTING_PERIOD + 1);
+    market.finalize(bank, id).unwrap();
+    assert_eq!(market.proposal_account(bank, id).status, ProposalStatus::Passed);
+}
+
+// Creates a Risk proposal (20% quorum, more than 60% in favour) from a proposer who doesn't vote
+fn create_risk_proposal(bank: &mut Bank, market: &Market, id: u64) {
+    create_proposal_of_type(bank, market, id, ProposalType::UpdateMinHealthFactor(15_000));
+}
+
+// Creates a proposal of the given type from a proposer who doesn't vote
+fn create_proposal_of_type(bank: &mut Bank, market: &Market, id: u64, proposal_type: ProposalType) {
+    let proposer = market.add_voter(bank, 100_000, FULL_LOCK);
+    advance_clock(1);
+    market.create_proposal(bank, proposer, id, proposal_type).unwrap();
+}
+
+// Puts a Risk proposal to voters with full-weight locks of the given balances, and finalizes it
+fn outcome(votes: &[(u64, VoteType)]) -> ProposalStatus {
+    let votes = votes.iter().map(|(balance, vote_type)| (*balance, FULL_LOCK, *vote_type)).collect::<Vec<_>>();
+    outcome_with_locks(&votes)
+}
+
+// Puts a Risk proposal to voters with the given balances, locked for the given times, and finalizes it
+fn outcome_with_locks(votes: &[(u64, i64, VoteType)]) -> ProposalStatus {
+    let (mut bank, market) = setup();
+    let voters = votes
+        .iter()
+        .map(|(balance, unlock_in, vote_type)| (market.add_voter(&mut bank, *balance, *unlock_in), *vote_type))
+        .collect::<Vec<_>>();
+    create_risk_proposal(&mut bank, &market, 0);
+    for (voter, vote_type) in voters {
+        market.vote(&mut bank, voter, 0, vote_type).unwrap();
+    }
+    advance_clock(DEFAULT_VOTING_PERIOD + 1);
+    market.finalize(&mut bank, 0).unwrap();
+    market.proposal_account(&bank, 0).status
+}
+
+fn update_config(bank: &mut Bank, market: &Market, authority: Pubkey, min_health_factor_bps: u64) -> ProgramResult {
+    bank.process(
+        accounts::UpdateConfig { authority, config_account: market.config },
+        instruction::UpdateConfig { min_health_factor_bps },
+    )
+}
+
+#[test]
+fn admin_instructions_run_through_proposals_once_governance_holds_the_authority() {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 600_000, DEFAULT_MAX_LOCK_DURATION);
+
+    // The admin hands the market to governance, and can't act on their own any more
+    bank.process(
+        accounts::SetAuthority { authority: market.authority, config_account: market.config },
+        instruction::SetAuthority { new_authority: market.governance_authority },
+    )
+    .unwrap();
+    assert_eq!(market.config(&bank).authority, market.governance_authority);
+    assert!(update_config(&mut bank, &market, market.authority, 15_000).is_err());
+
+    // Governance calls update_config itself, signing as the market's authority
+    let update_config_instruction = GovernanceInstruction {
+        program_id: stablecoin::ID,
+        accounts: accounts::UpdateConfig { authority: market.governance_authority, config_account: market.config }
+            .to_account_metas(None)
+            .into_iter()
+            .map(|meta| GovernanceAccountMeta { pubkey: meta.pubkey, is_signer: meta.is_signer, is_writable: meta.is_writable })
+            .collect(),
+        data: instruction::UpdateConfig { min_health_factor_bps: 15_000 }.data(),
+    };
+    advance_clock(1);
+    market
+        .create_proposal(&mut bank, voter, 0, ProposalType::ExecuteInstructions(vec![update_config_instruction]))
+        .unwrap();
+    pass(&mut bank, &market, voter, 0);
+
+    let remaining_accounts = vec![
+        AccountMeta::new_readonly(market.governance_authority, false),
+        AccountMeta::new(market.config, false),
+        AccountMeta::new_readonly(stablecoin::ID, false),
+    ];
+    assert_eq!(
+        market.execute(&mut bank, 0, remaining_accounts.clone()),
+        Err(error(CustomError::ExecutionDelayNotSatisfied))
+    );
+    advance_clock(DEFAULT_EXECUTION_DELAY);
+    market.execute(&mut bank, 0, remaining_accounts).unwrap();
+
+    assert_eq!(market.config(&bank).min_health_factor_bps, 15_000);
+    assert_eq!(market.proposal_account(&bank, 0).status, ProposalStatus::Executed);
+}
+
+#[test]
+fn the_market_cannot_be_handed_to_nobody() {
+    let (mut bank, market) = setup();
+
+    let result = bank.process(
+        accounts::SetAuthority { authority: market.authority, config_account: market.config },
+        instruction::SetAuthority { new_authority: Pubkey::default() },
+    );
+    assert_eq!(result, Err(error(CustomError::InvalidParameter)));
+    assert_eq!(market.config(&bank).authority, market.authority);
+}
+
+#[test]
+fn a_proposal_needs_more_than_its_approval_share_to_pass() {
+    use VoteType::{Against, For};
+
+    // Exactly 60% in favour isn't enough for a Risk proposal, a single vote more is
+    assert_eq!(outcome(&[(600_000, For), (400_000, Against)]), ProposalStatus::Rejected);
+    assert_eq!(outcome(&[(600_001, For), (399_999, Against)]), ProposalStatus::Passed);
+    assert_eq!(outcome(&[(300_000, For)]), ProposalStatus::Passed);
+    assert_eq!(outcome(&[(300_000, Against)]), ProposalStatus::Rejected);
+    assert_eq!(outcome(&[]), ProposalStatus::Rejected);
+}
+
+#[test]
+fn a_proposal_is_finalized_once_voting_ends_and_only_once() {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    create_risk_proposal(&mut bank, &market, 0);
+    market.vote(&mut bank, voter, 0, VoteType::For).unwrap();
+
+    advance_clock(DEFAULT_VOTING_PERIOD - 1);
+    assert_eq!(market.finalize(&mut bank, 0), Err(error(CustomError::ProposalVotingInProgress)));
+
+    advance_clock(2);
+    assert_eq!(
+        market.vote(&mut bank, voter, 0, VoteType::Against),
+        Err(error(CustomError::ProposalVotingEnded))
+    );
+    market.finalize(&mut bank, 0).unwrap();
+    let proposal = market.proposal_account(&bank, 0);
+    assert_eq!(proposal.status, ProposalStatus::Passed);
+    assert_eq!((proposal.votes_for, proposal.votes_against), (300_000, 0));
+
+    assert_eq!(market.finalize(&mut bank, 0), Err(error(CustomError::ProposalAlreadyFinalized)));
+}
+
+// The proposal's for and against tallies and the locked balance behind them
+fn tallies(bank: &Bank, market: &Market, id: u64) -> (u64, u64, u64) {
+    let proposal = market.proposal_account(bank, id);
+    (proposal.votes_for, proposal.votes_against, proposal.quorum_balance)
+}
+
+#[test]
+fn changing_and_withdrawing_a_vote_keeps_every_tally_in_step() {
+    let (mut bank, market) = setup();
+    let alice = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    let bob = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    create_risk_proposal(&mut bank, &market, 0);
+
+    market.vote(&mut bank, alice, 0, VoteType::For).unwrap();
+    market.vote(&mut bank, bob, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 200_000, 500_000));
+
+    // Changing a vote moves the power across without counting it twice
+    market.vote(&mut bank, alice, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 500_000, 500_000));
+    market.vote(&mut bank, alice, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 500_000, 500_000));
+    let record = market.vote_record_account(&bank, 0, &alice);
+    assert_eq!((record.vote_type, record.voting_power, record.balance), (VoteType::Against, 300_000, 300_000));
+
+    // Withdrawing takes the vote out entirely and closes its record
+    market.withdraw_vote(&mut bank, alice, 0).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 200_000, 200_000));
+    assert!(!bank.exists(&market.vote_record(0, &alice)));
+    assert!(market.withdraw_vote(&mut bank, alice, 0).is_err());
+
+    // A withdrawn voter can vote again
+    market.vote(&mut bank, alice, 0, VoteType::For).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 200_000, 500_000));
+
+    // Once voting ends, votes stay as they are
+    advance_clock(DEFAULT_VOTING_PERIOD + 1);
+    assert_eq!(market.withdraw_vote(&mut bank, bob, 0), Err(error(CustomError::ProposalVotingEnded)));
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 200_000, 500_000));
+}
+
+#[test]
+fn a_voter_without_a_lock_cannot_vote() {
+    let (mut bank, market) = setup();
+    create_risk_proposal(&mut bank, &market, 0);
+
+    // No voter weight account means no vote record either
+    let stranger = Pubkey::new_unique();
+    bank.fund(stranger, 1_000_000_000);
+    assert_eq!(market.vote(&mut bank, stranger, 0, VoteType::For), Err(error(CustomError::NoVotingPower)));
+    assert!(!bank.exists(&market.vote_record(0, &stranger)));
+    assert_eq!(tallies(&bank, &market, 0), (0, 0, 0));
+}
+
+#[test]
+fn tokens_moved_after_the_snapshot_do_not_vote_again() {
+    let (mut bank, market) = setup();
+    let alice = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    create_risk_proposal(&mut bank, &market, 0);
+    advance_clock(1);
+
+    // Only a shutdown lets a lock be withdrawn early, so alice can hand her tokens to bob, who locks them himself
+    let mut config = market.config(&bank);
+    config.emergency_shutdown = true;
+    market.set_config(&mut bank, &config);
+    let (_, alice_wallet) = market.add_holder(&mut bank, 0);
+    market.withdraw(&mut bank, alice, alice_wallet, 300_000).unwrap();
+    assert_eq!(bank.token_balance(&alice_wallet), 300_000);
+
+    let (bob, bob_wallet) = market.add_holder(&mut bank, 300_000);
+    market.deposit(&mut bank, bob, bob_wallet, 300_000).unwrap();
+    market.extend_lock(&mut bank, bob, DEFAULT_MAX_LOCK_DURATION).unwrap();
+    assert_eq!(bank.token_balance(&market.voter_vault(&bob)), 300_000);
+
+    // Alice still votes with what she had locked at the snapshot, bob's lock came too late to count
+    market.vote(&mut bank, alice, 0, VoteType::For).unwrap();
+    market.vote(&mut bank, bob, 0, VoteType::For).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 0, 300_000));
+    let record = market.vote_record_account(&bank, 0, &bob);
+    assert_eq!((record.voting_power, record.balance), (0, 0));
+}
+
+#[test]
+fn tokens_locked_in_the_proposals_own_slot_miss_its_snapshot() {
+    let (mut bank, market) = setup();
+    let (carol, carol_wallet) = market.add_holder(&mut bank, 300_000);
+    advance_clock(1);
+
+    // Carol locks in the same slot the proposal is created in, which the snapshot just misses
+    market.deposit(&mut bank, carol, carol_wallet, 300_000).unwrap();
+    market.extend_lock(&mut bank, carol, DEFAULT_MAX_LOCK_DURATION).unwrap();
+    let proposer = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    market.create_proposal(&mut bank, proposer, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+
+    market.vote(&mut bank, carol, 0, VoteType::For).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 0, 0));
+
+    // A proposal created a slot later sees her lock
+    advance_clock(1);
+    market.create_proposal(&mut bank, proposer, 1, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+    market.vote(&mut bank, carol, 1, VoteType::For).unwrap();
+    assert_eq!(tallies(&bank, &market, 1).2, 300_000);
+}
+
+#[test]
+fn voting_power_decays_with_the_time_left_on_the_lock() {
+    let (mut bank, market) = setup();
+    let half = market.add_voter(&mut bank, 400_000, DEFAULT_MAX_LOCK_DURATION / 2 + 1);
+    let quarter = market.add_voter(&mut bank, 400_000, DEFAULT_MAX_LOCK_DURATION / 4 + 1);
+    let expired = market.add_voter(&mut bank, 400_000, 1);
+    create_risk_proposal(&mut bank, &market, 0);
+
+    // Power is measured when the proposal was created, so voting late in the period costs nothing
+    market.vote(&mut bank, half, 0, VoteType::For).unwrap();
+    advance_clock(DEFAULT_VOTING_PERIOD);
+    market.vote(&mut bank, quarter, 0, VoteType::Against).unwrap();
+    market.vote(&mut bank, expired, 0, VoteType::Against).unwrap();
+    assert_eq!(market.vote_record_account(&bank, 0, &half).voting_power, 200_000);
+    assert_eq!(market.vote_record_account(&bank, 0, &quarter).voting_power, 100_000);
+    assert_eq!(market.vote_record_account(&bank, 0, &expired).voting_power, 0);
+    assert_eq!(tallies(&bank, &market, 0).0, 200_000);
+    assert_eq!(tallies(&bank, &market, 0).1, 100_000);
+
+    // A proposal created a quarter of the longest lock later finds every lock weaker (creating it takes a second)
+    advance_clock(DEFAULT_MAX_LOCK_DURATION / 4 - DEFAULT_VOTING_PERIOD - 1);
+    create_risk_proposal(&mut bank, &market, 1);
+    market.vote(&mut bank, half, 1, VoteType::For).unwrap();
+    market.vote(&mut bank, quarter, 1, VoteType::For).unwrap();
+    assert_eq!(market.vote_record_account(&bank, 1, &half).voting_power, 100_000);
+    assert_eq!(market.vote_record_account(&bank, 1, &quarter).voting_power, 0);
+}
+
+#[test]
+fn extending_a_lock_restores_its_power_and_proposers_need_current_power() {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 150_000, DEFAULT_MAX_LOCK_DURATION / 2 + 1);
+    advance_clock(1);
+
+    // Half a lock on 150,000 tokens is only 75,000 voting power, short of the proposal threshold
+    assert_eq!(
+        market.create_proposal(&mut bank, voter, 0, ProposalType::UpdateMinHealthFactor(15_000)),
+        Err(error(CustomError::InsufficientGovernanceBalance))
+    );
+
+    // Locks only get longer, never past the market's longest lock
+    assert_eq!(market.extend_lock(&mut bank, voter, 1_000), Err(error(CustomError::InvalidParameter)));
+    assert_eq!(
+        market.extend_lock(&mut bank, voter, DEFAULT_MAX_LOCK_DURATION + 1),
+        Err(error(CustomError::InvalidParameter))
+    );
+    market.extend_lock(&mut bank, voter, DEFAULT_MAX_LOCK_DURATION).unwrap();
+    market.create_proposal(&mut bank, voter, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+}
+
+#[test]
+fn a_proposal_needs_its_quorum_of_the_supply_to_vote() {
+    use VoteType::{Against, For};
+
+    // A Risk proposal needs 20% of the 1,000,000 supply, whichever way it votes
+    assert_eq!(outcome(&[(199_999, For)]), ProposalStatus::Rejected);
+    assert_eq!(outcome(&[(200_000, For)]), ProposalStatus::Passed);
+    assert_eq!(outcome(&[(150_000, For), (50_000, Against)]), ProposalStatus::Passed);
+    assert_eq!(outcome(&[(150_000, For), (49_999, Against)]), ProposalStatus::Rejected);
+}
+
+#[test]
+fn quorum_counts_locked_balances_not_decayed_power() {
+    use VoteType::For;
+
+    // Half a lock on 200,000 tokens only votes with 100,000, but all 200,000 count towards quorum
+    assert_eq!(outcome_with_locks(&[(200_000, DEFAULT_MAX_LOCK_DURATION / 2 + 1, For)]), ProposalStatus::Passed);
+    assert_eq!(outcome_with_locks(&[(199_999, DEFAULT_MAX_LOCK_DURATION / 2 + 1, For)]), ProposalStatus::Rejected);
+
+    // A lock that has already run out counts for nothing
+    assert_eq!(outcome_with_locks(&[(150_000, FULL_LOCK, For), (1_000_000, 1, For)]), ProposalStatus::Rejected);
+}
+
+#[test]
+fn quorum_is_fixed_from_the_supply_when_the_proposal_is_created() {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    create_risk_proposal(&mut bank, &market, 0);
+    assert_eq!(market.proposal_account(&bank, 0).quorum_votes, 200_000);
+
+    // Governance tokens issued while voting is open don't raise the bar
+    market.set_governance_supply(&mut bank, 2 * SUPPLY);
+    pass(&mut bank, &market, voter, 0);
+    assert_eq!(tallies(&bank, &market, 0), (200_000, 0, 200_000));
+}
+
+// A Risk proposal that has passed and is waiting out its timelock, with the voter who passed it
+fn passed_proposal() -> (Bank, Market, Pubkey) {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    create_risk_proposal(&mut bank, &market, 0);
+    pass(&mut bank, &market, voter, 0);
+    (bank, market, voter)
+}
+
+#[test]
+fn a_passed_proposal_executes_within_its_window_and_expires_after() {
+    let (mut bank, market, _) = passed_proposal();
+    let proposal = market.proposal_account(&bank, 0);
+    assert_eq!(proposal.executable_at, proposal.finalized_at + DEFAULT_EXECUTION_DELAY);
+    assert_eq!(proposal.expires_at, proposal.executable_at + DEFAULT_EXECUTION_WINDOW);
+
+    advance_clock(DEFAULT_EXECUTION_DELAY + DEFAULT_EXECUTION_WINDOW);
+    market.execute(&mut bank, 0, vec![]).unwrap();
+    assert_eq!(market.config(&bank).min_health_factor_bps, 15_000);
+
+    let (mut bank, market, _) = passed_proposal();
+    advance_clock(DEFAULT_EXECUTION_DELAY + DEFAULT_EXECUTION_WINDOW + 1);
+    assert_eq!(market.execute(&mut bank, 0, vec![]), Err(error(CustomError::ProposalExpired)));
+    assert_ne!(market.config(&bank).min_health_factor_bps, 15_000);
+}
+
+#[test]
+fn changing_the_rules_does_not_move_a_passed_proposals_timelock() {
+    let (mut bank, market, _) = passed_proposal();
+
+    // A shorter delay doesn't let the proposal run early
+    let mut governance_config = market.governance_config(&bank);
+    governance_config.execution_delay = DEFAULT_EXECUTION_DELAY / 2;
+    market.set_governance_config(&mut bank, &governance_config);
+    advance_clock(DEFAULT_EXECUTION_DELAY / 2);
+    assert_eq!(market.execute(&mut bank, 0, vec![]), Err(error(CustomError::ExecutionDelayNotSatisfied)));
+
+    // And a longer one doesn't hold it back once its own delay is over
+    governance_config.execution_delay = 2 * DEFAULT_EXECUTION_DELAY;
+    market.set_governance_config(&mut bank, &governance_config);
+    advance_clock(DEFAULT_EXECUTION_DELAY / 2);
+    market.execute(&mut bank, 0, vec![]).unwrap();
+    assert_eq!(market.proposal_account(&bank, 0).status, ProposalStatus::Executed);
+}
+
+#[test]
+fn the_guardian_can_veto_a_proposal_in_its_timelock() {
+    let (mut bank, market, voter) = passed_proposal();
+
+    // Once voting is over the proposer can't take it back, and nobody else ever could
+    assert_eq!(market.cancel(&mut bank, voter, 0), Err(error(CustomError::CannotCancelProposal)));
+    assert_eq!(market.cancel(&mut bank, Pubkey::new_unique(), 0), Err(error(CustomError::CannotCancelProposal)));
+
+    // The market's admin starts out as its guardian
+    market.cancel(&mut bank, market.authority, 0).unwrap();
+    assert_eq!(market.proposal_account(&bank, 0).status, ProposalStatus::Cancelled);
+
+    advance_clock(DEFAULT_EXECUTION_DELAY);
+    assert!(market.execute(&mut bank, 0, vec![]).is_err());
+    assert_ne!(market.config(&bank).min_health_factor_bps, 15_000);
+    assert_eq!(market.cancel(&mut bank, market.authority, 0), Err(error(CustomError::CannotCancelProposal)));
+}
+
+#[test]
+fn the_proposer_can_cancel_only_while_voting_is_open() {
+    let (mut bank, market) = setup();
+    let proposer = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    advance_clock(1);
+    market.create_proposal(&mut bank, proposer, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+    market.create_proposal(&mut bank, proposer, 1, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+
+    market.cancel(&mut bank, proposer, 0).unwrap();
+    assert_eq!(market.proposal_account(&bank, 0).status, ProposalStatus::Cancelled);
+    assert_eq!(market.vote(&mut bank, proposer, 0, VoteType::For), Err(error(CustomError::ProposalVotingEnded)));
+
+    advance_clock(DEFAULT_VOTING_PERIOD + 1);
+    assert_eq!(market.cancel(&mut bank, proposer, 1), Err(error(CustomError::CannotCancelProposal)));
+}
+
+#[test]
+fn removing_the_guardian_removes_the_veto() {
+    let (mut bank, market, _) = passed_proposal();
+
+    let mut governance_config = market.governance_config(&bank);
+    governance_config.guardian = Pubkey::default();
+    market.set_governance_config(&mut bank, &governance_config);
+    assert_eq!(market.cancel(&mut bank, Pubkey::default(), 0), Err(error(CustomError::CannotCancelProposal)));
+    assert_eq!(market.cancel(&mut bank, market.authority, 0), Err(error(CustomError::CannotCancelProposal)));
+}
+
+#[test]
+fn a_delegator_voting_directly_overrides_their_delegate() {
+    let (mut bank, market) = setup();
+    let alice = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    let dave = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    market.delegate(&mut bank, alice, dave).unwrap();
+    create_risk_proposal(&mut bank, &market, 0);
+
+    // Dave votes first, with alice's power on her own record
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[alice]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 0, 300_000));
+    let record = market.vote_record_account(&bank, 0, &alice);
+    assert_eq!((record.cast_by, record.vote_type, record.voting_power), (dave, VoteType::For, 200_000));
+
+    // Alice can't withdraw a vote she didn't cast, but she can replace it
+    assert_eq!(market.withdraw_vote(&mut bank, alice, 0), Err(error(CustomError::NoVoteCast)));
+    market.vote(&mut bank, alice, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (100_000, 200_000, 300_000));
+    let record = market.vote_record_account(&bank, 0, &alice);
+    assert_eq!((record.cast_by, record.vote_type, record.voting_power), (alice, VoteType::Against, 200_000));
+
+    // From then on dave's votes leave hers alone
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[alice]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (100_000, 200_000, 300_000));
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::Against, &[alice]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 300_000, 300_000));
+    assert_eq!(market.vote_record_account(&bank, 0, &alice).cast_by, alice);
+
+    // And dave withdrawing takes back only his own vote
+    market.withdraw_vote(&mut bank, dave, 0).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 200_000, 200_000));
+}
+
+#[test]
+fn a_delegate_changing_their_vote_moves_their_delegators_with_them() {
+    let (mut bank, market) = setup();
+    let alice = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    let bob = market.add_voter(&mut bank, 50_000, FULL_LOCK);
+    let dave = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    market.delegate(&mut bank, alice, dave).unwrap();
+    market.delegate(&mut bank, bob, dave).unwrap();
+    create_risk_proposal(&mut bank, &market, 0);
+
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[alice, bob]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (350_000, 0, 350_000));
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::Against, &[alice, bob]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 350_000, 350_000));
+    assert_eq!(market.vote_record_account(&bank, 0, &bob).vote_type, VoteType::Against);
+}
+
+#[test]
+fn only_power_delegated_to_the_voter_at_the_snapshot_counts() {
+    let (mut bank, market) = setup();
+    let alice = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    let bob = market.add_voter(&mut bank, 50_000, FULL_LOCK);
+    let dave = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    let erin = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    assert_eq!(market.delegate(&mut bank, alice, alice), Err(error(CustomError::InvalidDelegate)));
+    assert_eq!(market.delegate(&mut bank, alice, Pubkey::default()), Err(error(CustomError::InvalidDelegate)));
+    assert_eq!(market.undelegate(&mut bank, alice), Err(error(CustomError::InvalidDelegate)));
+    market.delegate(&mut bank, alice, dave).unwrap();
+    create_risk_proposal(&mut bank, &market, 0);
+
+    // Erin can't vote alice's power, and bob delegated after the snapshot
+    assert_eq!(
+        market.vote_for_delegators(&mut bank, erin, 0, VoteType::For, &[alice]),
+        Err(error(CustomError::NotDelegated))
+    );
+    market.delegate(&mut bank, bob, dave).unwrap();
+    assert_eq!(
+        market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[bob]),
+        Err(error(CustomError::NotDelegated))
+    );
+    assert_eq!(tallies(&bank, &market, 0), (0, 0, 0));
+
+    // Taking the delegation back doesn't reach into a proposal already snapshotted, only later ones
+    market.undelegate(&mut bank, alice).unwrap();
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[alice]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 0, 300_000));
+
+    create_risk_proposal(&mut bank, &market, 1);
+    assert_eq!(
+        market.vote_for_delegators(&mut bank, dave, 1, VoteType::For, &[alice]),
+        Err(error(CustomError::NotDelegated))
+    );
+    market.vote_for_delegators(&mut bank, dave, 1, VoteType::For, &[bob]).unwrap();
+
+    // The locks have decayed a little by now, so I add up what was recorded
+    let power = market.vote_record_account(&bank, 1, &dave).voting_power + market.vote_record_account(&bank, 1, &bob).voting_power;
+    assert_eq!(tallies(&bank, &market, 1), (power, 0, 150_000));
+    assert_eq!(market.vote_record_account(&bank, 1, &bob).cast_by, dave);
+}
+
+#[test]
+fn votes_on_a_closed_proposal_do_not_carry_over_to_its_id_reused() {
+    let (mut bank, market) = setup();
+    let proposer = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    let alice = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    let bob = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    advance_clock(1);
+    market.create_proposal(&mut bank, proposer, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+    market.vote(&mut bank, alice, 0, VoteType::For).unwrap();
+
+    // Everything below happens in the same second, so the new proposal has the old one's created_at
+    let created_at = market.proposal_account(&bank, 0).created_at;
+    market.cancel(&mut bank, proposer, 0).unwrap();
+    market.close_proposal(&mut bank, proposer, 0).unwrap();
+    market.create_proposal(&mut bank, proposer, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+    let proposal = market.proposal_account(&bank, 0);
+    assert_eq!(proposal.created_at, created_at);
+    assert_eq!(proposal.nonce, 2);
+    assert_eq!(market.governance_config(&bank).proposal_count, 2);
+
+    // Alice's old record is still there, but it never counted towards this proposal
+    market.vote(&mut bank, bob, 0, VoteType::For).unwrap();
+    assert_eq!(market.withdraw_vote(&mut bank, alice, 0), Err(error(CustomError::NoVoteCast)));
+    assert_eq!(tallies(&bank, &market, 0), (200_000, 0, 200_000));
+
+    // She can vote on it afresh
+    market.vote(&mut bank, alice, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (200_000, 300_000, 500_000));
+    market.withdraw_vote(&mut bank, alice, 0).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (200_000, 0, 200_000));
+}
+
+#[test]
+fn changing_a_vote_on_a_recreated_proposal_starts_from_a_clean_record() {
+    let (mut bank, market) = setup();
+    let proposer = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    let alice = market.add_voter(&mut bank, 300_000, FULL_LOCK);
+    let bob = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    let dave = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    market.delegate(&mut bank, bob, dave).unwrap();
+    advance_clock(1);
+    market.create_proposal(&mut bank, proposer, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+    market.vote(&mut bank, alice, 0, VoteType::For).unwrap();
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[bob]).unwrap();
+    market.cancel(&mut bank, proposer, 0).unwrap();
+    market.close_proposal(&mut bank, proposer, 0).unwrap();
+    market.create_proposal(&mut bank, proposer, 0, ProposalType::UpdateMinHealthFactor(15_000)).unwrap();
+
+    // Alice's and bob's old records are stale, so nothing is taken back from the new tallies
+    market.vote(&mut bank, alice, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 300_000, 300_000));
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[bob]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (300_000, 300_000, 600_000));
+
+    // And bob voting himself replaces dave's vote for him once, not twice
+    market.vote(&mut bank, bob, 0, VoteType::Against).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (100_000, 500_000, 600_000));
+    assert_eq!(market.vote_record_account(&bank, 0, &bob).proposal_nonce, market.proposal_account(&bank, 0).nonce);
+}
+
+#[test]
+fn registering_a_facilitator_is_critical_and_binds_its_programs_pda() {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 400_000, FULL_LOCK);
+    let program_id = Pubkey::new_unique();
+    create_proposal_of_type(&mut bank, &market, 0, ProposalType::SetFacilitator { program_id, bucket_capacity: 1_000 });
+
+    // Critical proposals need 30% of the supply to vote, and two thirds in favour
+    let proposal = market.proposal_account(&bank, 0);
+    assert_eq!((proposal.quorum_votes, proposal.approval_bps), (300_000, 6_667));
+    pass(&mut bank, &market, voter, 0);
+
+    advance_clock(DEFAULT_EXECUTION_DELAY);
+    let (facilitator_account, _) = Pubkey::find_program_address(
+        &[SEED_FACILITATOR, market.config.as_ref(), program_id.as_ref()],
+        &::stablecoin::ID,
+    );
+    let accounts = accounts::ExecuteProposal {
+        facilitator: Some(facilitator_account),
+        ..market.execute_accounts(&mut bank, 0)
+    };
+    bank.process(accounts, instruction::ExecuteProposal { proposal_id: 0 }).unwrap();
+
+    let facilitator: Facilitator = bank.anchor_account(&facilitator_account);
+    assert_eq!(facilitator.program_id, program_id);
+    assert_eq!(facilitator.facilitator, Facilitator::signer(&program_id, &market.config));
+    assert_eq!((facilitator.bucket_capacity, facilitator.bucket_level), (1_000, 0));
+}
+
+// A proposal of the given type that has passed and waited out its timelock
+fn ready_to_execute(proposal_type: ProposalType) -> (Bank, Market) {
+    let (mut bank, market) = setup();
+    let voter = market.add_voter(&mut bank, 400_000, FULL_LOCK);
+    create_proposal_of_type(&mut bank, &market, 0, proposal_type);
+    pass(&mut bank, &market, voter, 0);
+    advance_clock(DEFAULT_EXECUTION_DELAY);
+    (bank, market)
+}
+
+fn emode_category(market: &Market, id: u8) -> Pubkey {
+    Pubkey::find_program_address(&[SEED_EMODE_CATEGORY, market.config.as_ref(), &[id]], &::stablecoin::ID).0
+}
+
+#[test]
+fn proposals_that_do_not_need_the_optional_accounts_leave_them_alone() {
+    let (mut bank, market) = ready_to_execute(ProposalType::UpdateMinHealthFactor(15_000));
+
+    // Passing the accounts a category or bucket would live at doesn't create them
+    let unrelated_category = emode_category(&market, NO_EMODE_CATEGORY);
+    let (unrelated_bucket, _) =
+        Pubkey::find_program_address(&[SEED_FACILITATOR, market.config.as_ref()], &::stablecoin::ID);
+    let accounts = accounts::ExecuteProposal {
+        emode_category: Some(unrelated_category),
+        facilitator: Some(unrelated_bucket),
+        ..market.execute_accounts(&mut bank, 0)
+    };
+    bank.process(accounts, instruction::ExecuteProposal { proposal_id: 0 }).unwrap();
+    assert_eq!(market.config(&bank).min_health_factor_bps, 15_000);
+    assert!(!bank.exists(&unrelated_category));
+    assert!(!bank.exists(&unrelated_bucket));
+}
+
+#[test]
+fn an_emode_proposal_creates_only_the_category_it_names() {
+    let update = ProposalType::UpdateEModeCategory {
+        id: 1,
+        includes_sol: true,
+        liquidation_threshold: 90,
+        liquidation_bonus: 3,
+        min_collateral_ratio: 105,
+    };
+    let (mut bank, market) = ready_to_execute(update.clone());
+
+    let accounts = accounts::ExecuteProposal {
+        emode_category: Some(emode_category(&market, 2)),
+        ..market.execute_accounts(&mut bank, 0)
+    };
+    assert_eq!(
+        bank.process(accounts, instruction::ExecuteProposal { proposal_id: 0 }),
+        Err(error(CustomError::MissingEModeCategory))
+    );
+    assert_eq!(market.execute(&mut bank, 0, vec![]), Err(error(CustomError::MissingEModeCategory)));
+
+    let accounts = accounts::ExecuteProposal {
+        emode_category: Some(emode_category(&market, 1)),
+        ..market.execute_accounts(&mut bank, 0)
+    };
+    bank.process(accounts, instruction::ExecuteProposal { proposal_id: 0 }).unwrap();
+    let category: EModeCategory = bank.anchor_account(&emode_category(&market, 1));
+    assert_eq!((category.id, category.includes_sol, category.liquidation_threshold), (1, true, 90));
+    assert_eq!((category.liquidation_bonus, category.min_collateral_ratio), (3, 105));
+    assert!(!bank.exists(&emode_category(&market, 2)));
+}
+
+#[test]
+fn a_facilitator_proposal_needs_its_own_bucket() {
+    let program_id = Pubkey::new_unique();
+    let (mut bank, market) = ready_to_execute(ProposalType::SetFacilitator { program_id, bucket_capacity: 1_000 });
+
+    let (other_bucket, _) = Pubkey::find_program_address(
+        &[SEED_FACILITATOR, market.config.as_ref(), Pubkey::new_unique().as_ref()],
+        &::stablecoin::ID,
+    );
+    let accounts = accounts::ExecuteProposal {
+        facilitator: Some(other_bucket),
+        ..market.execute_accounts(&mut bank, 0)
+    };
+    assert_eq!(
+        bank.process(accounts, instruction::ExecuteProposal { proposal_id: 0 }),
+        Err(error(CustomError::MissingFacilitatorAccount))
+    );
+    assert_eq!(market.execute(&mut bank, 0, vec![]), Err(error(CustomError::MissingFacilitatorAccount)));
+    assert!(!bank.exists(&other_bucket));
+}
+
+#[test]
+fn resetting_a_facilitators_capacity_keeps_what_it_has_minted() {
+    let program_id = Pubkey::new_unique();
+    let (mut bank, market) = ready_to_execute(ProposalType::SetFacilitator { program_id, bucket_capacity: 200 });
+    let (bucket, bump) = Pubkey::find_program_address(
+        &[SEED_FACILITATOR, market.config.as_ref(), program_id.as_ref()],
+        &::stablecoin::ID,
+    );
+    let facilitator = Facilitator::signer(&program_id, &market.config);
+    bank.set_anchor_account(
+        bucket,
+        &Facilitator { market: market.config, program_id, facilitator, bucket_capacity: 1_000, bucket_level: 300, bump },
+        8 + Facilitator::INIT_SPACE,
+    );
+
+    let accounts = accounts::ExecuteProposal { facilitator: Some(bucket), ..market.execute_accounts(&mut bank, 0) };
+    bank.process(accounts, instruction::ExecuteProposal { proposal_id: 0 }).unwrap();
+    let facilitator: Facilitator = bank.anchor_account(&bucket);
+    assert_eq!((facilitator.bucket_capacity, facilitator.bucket_level), (200, 300));
+}
+
+#[test]
+fn a_delegate_without_a_lock_of_their_own_can_vote_delegated_power() {
+    let (mut bank, market) = setup();
+    let alice = market.add_voter(&mut bank, 200_000, FULL_LOCK);
+    let bob = market.add_voter(&mut bank, 50_000, FULL_LOCK);
+    let (dave, _) = market.add_holder(&mut bank, 0);
+    market.delegate(&mut bank, alice, dave).unwrap();
+    market.delegate(&mut bank, bob, dave).unwrap();
+    create_risk_proposal(&mut bank, &market, 0);
+    assert!(!bank.exists(&market.voter_weight(&dave)));
+
+    // Dave has nothing of his own to vote with, so he needs someone to vote for
+    assert_eq!(market.vote(&mut bank, dave, 0, VoteType::For), Err(error(CustomError::NoVotingPower)));
+
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[alice, bob]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (250_000, 0, 250_000));
+    let record = market.vote_record_account(&bank, 0, &dave);
+    assert_eq!((record.voting_power, record.balance), (0, 0));
+    let record = market.vote_record_account(&bank, 0, &alice);
+    assert_eq!((record.cast_by, record.voting_power), (dave, 200_000));
+
+    // He can change his vote, and his delegators can still override him
+    market.vote_for_delegators(&mut bank, dave, 0, VoteType::Against, &[alice, bob]).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (0, 250_000, 250_000));
+    market.vote(&mut bank, alice, 0, VoteType::For).unwrap();
+    assert_eq!(tallies(&bank, &market, 0), (200_000, 50_000, 250_000));
+
+    // And power that wasn't delegated to him stays out of reach
+    let carol = market.add_voter(&mut bank, 100_000, FULL_LOCK);
+    assert_eq!(
+        market.vote_for_delegators(&mut bank, dave, 0, VoteType::For, &[carol]),
+        Err(error(CustomError::NotDelegated))
+    );
+}
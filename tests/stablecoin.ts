@@ -12,18 +12,22 @@ describe("stablecoin", () => {
   anchor.setProvider(provider);
   const program = anchor.workspace.stablecoin as Program<Stablecoin>;
   const pythSolanaReciever = new PythSolanaReceiver({connection, wallet});
-  const SOL_PRICE_FEED_ID = "OxeredBb6fda2ceba41da15d4095dlda392a0d2f8ed0c6c7bcof4cfac8c280b56d";
+  const SOL_PRICE_FEED_ID = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
   const solUsdPriceFeedAccount = pythSolanaReciever.getPriceFeedAccountAddress(0, SOL_PRICE_FEED_ID);
 
+  // Market 0 is the USD market
+  const marketId = new anchor.BN(0);
+  const [configAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("config"), marketId.toArrayLike(Buffer, "le", 8)], program.programId);
   const [collateralAccount] = anchor.web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("collateral"), wallet.publicKey.toBuffer()], program.programId);
+    [Buffer.from("collateral"), configAccount.toBuffer(), wallet.publicKey.toBuffer()], program.programId);
 
 
   it("Is initialized!", async () => {
     
     const tx = await program.methods
-    .initializeConfig()
-    .accounts({})
+    .initializeConfig(marketId, new Array(32).fill(0))
+    .accounts({ configAccount, priceUpdate: solUsdPriceFeedAccount })
     .rpc({skipPreflight: true, commitment: "confirmed"});
 
     console.log("Transaction signature", tx);
@@ -37,7 +41,32 @@ describe("stablecoin", () => {
       new anchor.BN(amountCollateral),
       new anchor.BN(amountToMint)
     )
-    .accounts({ priceUpdate: solUsdPriceFeedAccount})
+    .accounts({ configAccount, priceUpdate: solUsdPriceFeedAccount})
+    .rpc({ skipPreflight: true, commitment: "confirmed"});
+
+    console.log("Transaction signature", tx);
+  });
+
+  it("Deposit Stake Collateral", async () => {
+    const stakeAccount = anchor.web3.Keypair.generate();
+    const { current } = await connection.getVoteAccounts();
+    const votePubkey = new anchor.web3.PublicKey(current[0].votePubkey);
+
+    const stakeTx = anchor.web3.StakeProgram.createAccount({
+      fromPubkey: wallet.publicKey,
+      stakePubkey: stakeAccount.publicKey,
+      authorized: new anchor.web3.Authorized(wallet.publicKey, wallet.publicKey),
+      lamports: 2 * anchor.web3.LAMPORTS_PER_SOL,
+    }).add(anchor.web3.StakeProgram.delegate({
+      stakePubkey: stakeAccount.publicKey,
+      authorizedPubkey: wallet.publicKey,
+      votePubkey,
+    }));
+    await provider.sendAndConfirm(stakeTx, [stakeAccount], { commitment: "confirmed" });
+
+    const tx = await program.methods
+    .depositStakeCollateral()
+    .accounts({ configAccount, stakeAccount: stakeAccount.publicKey })
     .rpc({ skipPreflight: true, commitment: "confirmed"});
 
     console.log("Transaction signature", tx);
@@ -52,7 +81,7 @@ describe("stablecoin", () => {
       new anchor.BN(amountCollateral),
       new anchor.BN(amountToBurn)
     )
-    .accounts({ priceUpdate: solUsdPriceFeedAccount})
+    .accounts({ configAccount, priceUpdate: solUsdPriceFeedAccount})
     .rpc({ skipPreflight: true, commitment: "confirmed"});
 
     console.log("Transaction signature", tx);
@@ -60,8 +89,8 @@ describe("stablecoin", () => {
 
   it("Update Config", async () => {
     const tx = await program.methods
-    .updateConfig(new anchor.BN(100))
-    .accounts({})
+    .updateConfig(new anchor.BN(30_000))
+    .accounts({ configAccount })
     .rpc({ skipPreflight: true, commitment: "confirmed"});
 
     console.log("Transaction signature", tx);
@@ -70,8 +99,8 @@ describe("stablecoin", () => {
   it("Liquidation", async () => {
      const amountToBurn = 500_000_000
      const tx = await program.methods
-    .liquidate(new anchor.BN(amountToBurn))
-    .accounts({collateralAccount, priceUpdate: solUsdPriceFeedAccount})
+    .liquidate(new anchor.BN(amountToBurn), { sol: {} })
+    .accounts({configAccount, collateralAccount, priceUpdate: solUsdPriceFeedAccount})
     .rpc({ skipPreflight: true, commitment: "confirmed"});
 
     console.log("Transaction signature", tx);
@@ -79,8 +108,8 @@ describe("stablecoin", () => {
 
     it("Update Config", async () => {
     const tx = await program.methods
-    .updateConfig(new anchor.BN(2))
-    .accounts({})
+    .updateConfig(new anchor.BN(20_000))
+    .accounts({ configAccount })
     .rpc({ skipPreflight: true, commitment: "confirmed"});
 
     console.log("Transaction signature", tx);